@@ -1,30 +1,26 @@
-#![feature(fn_traits)]
-
 use std::fs::File;
+use std::io::{BufReader, BufWriter};
 use std::time::Instant;
 
-use lazy_static::lazy_static;
 use rand::{Rng, SeedableRng};
 use rand::prelude::StdRng;
 
-use grid::{Offset, Point, Update};
+use grid::Point;
+use grid::animation::{FrameRecorder, Viewport};
+use grid::image::{threshold, Image, ImageRenderer, Rgb};
+use grid::pattern::{Blend, Transform};
+use grid::replay::{Recorder, ReplayLog};
+use grid::rule::LifeRule;
+use grid::run::StopCondition;
+use grid::snapshot::PrimitiveCodec;
+use grid::soup::Soup;
 use std::ops::Range;
+use std::process::exit;
 
-lazy_static! {
-    static ref MOORE_NEIGHBORS: Vec<Offset> = {
-        let mut n = Vec::new();
-        n.push(Offset::new(-1, -1));
-        n.push(Offset::new(-1, 0));
-        n.push(Offset::new(-1, 1));
-        n.push(Offset::new(0, -1));
-        n.push(Offset::new(0, 1));
-        n.push(Offset::new(1, -1));
-        n.push(Offset::new(1, 0));
-        n.push(Offset::new(1, 1));
-        n
-    };
-}
+const USAGE: &str = "usage: bin [--seed-image IMAGE] [--record LOG | --gif OUT]
+       bin --replay LOG GENERATION";
 
+#[allow(dead_code, unused_variables)]
 fn bench_divs() {
     let mut r = StdRng::seed_from_u64(2);
 
@@ -110,18 +106,47 @@ fn main() {
 
     //
 
-    let mut r = StdRng::seed_from_u64(2);
-
-    //
+    let args: Vec<String> = std::env::args().collect();
+    let flag = |name: &str| args.iter().position(|a| a == name).map(|i| &args[i + 1..]);
+
+    let rule = LifeRule::conway();
+
+    if let Some(rest) = flag("--replay") {
+        let path = arg(rest, 0, "--replay needs a log path and a generation");
+        let generation = arg(rest, 1, "--replay needs a log path and a generation")
+            .parse()
+            .unwrap_or_else(|_| fail("--replay generation must be a whole number"));
+        let file = File::open(path).unwrap_or_else(|e| fail(&format!("cannot open {}: {}", path, e)));
+        let log = ReplayLog::read(BufReader::new(file), &PrimitiveCodec)
+            .unwrap_or_else(|e| fail(&format!("cannot read replay log {}: {}", path, e)));
+
+        match log.verify::<_, _, 32>(&rule, &PrimitiveCodec) {
+            Ok(None) => println!("replay matches all {} recorded generations", log.generations()),
+            Ok(Some(g)) => println!("replay diverges at generation {}", g),
+            Err(e) => fail(&format!("cannot verify replay log {}: {}", path, e)),
+        }
+
+        let mut grid: grid::Grid<usize, 32> = log.replay(&rule, &PrimitiveCodec, generation)
+            .unwrap_or_else(|e| fail(&format!("cannot replay {} to generation {}: {}", path, generation, e)));
+        grid.print(|v| v != &0);
+        return;
+    }
 
     let mut grid: grid::Grid<usize, 32> = grid::Grid::new();
     grid.set(&grid::Point::new(0, 0), 0);
 
-    for _ in 0..250 {
-        let x: isize = r.gen_range(-7..8);
-        let z: isize = r.gen_range(-7..8);
-
-        grid.set(&grid::Point::new(x, z), 1);
+    match flag("--seed-image") {
+        Some(rest) => {
+            let path = arg(rest, 0, "--seed-image needs an image path");
+            let file = File::open(path).unwrap_or_else(|e| fail(&format!("cannot open {}: {}", path, e)));
+            let image = Image::read(BufReader::new(file)).unwrap_or_else(|e| fail(&format!("cannot read image {}: {}", path, e)));
+            grid.paste(&image.to_pattern(threshold(128, 1, 0)), &Point::new(0, 0), Transform::Identity, Blend::SkipDefault);
+        }
+        None => {
+            Soup::new(Point::new(-7, -7), Point::new(7, 7), 0.5, 1, 2)
+                .apply(&mut grid)
+                .unwrap();
+        }
     }
 
     //
@@ -130,41 +155,70 @@ fn main() {
 
     // return;
 
-    let start = Instant::now();
-
-    for i in 0..2000 {
-        grid.tick(|point| {
-            &MOORE_NEIGHBORS
-        }, |point, cur, neighbors| {
-            let mut r = Vec::with_capacity(1);
-
-            let mut live = neighbors.into_iter().filter(|v| v == &Some(&1)).count();
-
-            if cur.is_none() || cur == Some(&0) {
-                if live == 3 {
-                    r.push(Update::new(point.copy(), |_| Some(1)));
-                }
-            } else {
-                if live < 2 || live > 3 {
-                    r.push(Update::new(point.copy(), |_| Some(0)));
-                }
-            }
+    if let Some(rest) = flag("--record") {
+        let path = arg(rest, 0, "--record needs a log path");
+        let file = File::create(path).unwrap_or_else(|e| fail(&format!("cannot create {}: {}", path, e)));
+        let mut recorder = Recorder::new(grid, rule, 2, &PrimitiveCodec)
+            .unwrap_or_else(|e| fail(&format!("cannot start recording: {}", e)));
+        let start = Instant::now();
+        recorder.run(2000);
+        println!("{:?}", start.elapsed());
+
+        let (_, log) = recorder.finish();
+        if let Err(e) = log.write(BufWriter::new(file), &PrimitiveCodec) {
+            fail(&format!("cannot write replay log {}: {}", path, e));
+        }
+        return;
+    }
 
-            r
-        });
+    let frames = flag("--gif").map(|rest| {
+        let renderer = ImageRenderer::new(|v: &usize| if *v != 0 { Rgb::WHITE } else { Rgb::BLACK }).with_zoom(2);
+        let path = arg(rest, 0, "--gif needs an output path");
+        // A fixed viewport writes frames as they come instead of holding all of them until the end.
+        let (min, max) = grid.bounding_box().unwrap_or((Point::new(0, 0), Point::new(0, 0)));
+        let viewport = Viewport::Fixed {
+            start: Point::new(min.x() - 64, min.z() - 64),
+            end: Point::new(max.x() + 64, max.z() + 64),
+        };
+        let file = File::create(path).unwrap_or_else(|e| fail(&format!("cannot create {}: {}", path, e)));
+        let frames = FrameRecorder::gif(BufWriter::new(file), renderer, viewport)
+            .with_interval(10)
+            .with_generation_overlay()
+            .with_population_overlay();
+
+        if let Err(e) = frames.capture(&grid) {
+            fail(&format!("cannot write {}: {}", path, e));
+        }
+        grid.set_tick_hook(frames.hook());
+        frames
+    });
+
+    let report = grid.run(&rule, 2000, &[StopCondition::Extinction]);
+
+    println!("{:?} after {} generations", report.reason, report.generations);
+
+    if let Some(frames) = frames {
+        grid.clear_tick_hook();
+        match frames.finish() {
+            Ok(n) => println!("wrote {} frames", n),
+            Err(e) => fail(&format!("cannot finish the gif: {}", e)),
+        }
+    }
 
-        // println!("{}", i);
+    // std::fs::write("out.txt", grid.print(|v| v == &1));
 
-        //
-        println!("{}", i);
+    println!("{:?}", report.elapsed);
+}
 
-        // if i % 99 == 0 {
-        //     println!("{}", i);
-        // grid.print(|v| v != &0);
-        // }
+// The `i`th value after a flag, or the usage error `missing` if the command line ends first.
+fn arg<'a>(rest: &'a [String], i: usize, missing: &str) -> &'a str {
+    match rest.get(i) {
+        Some(v) => v,
+        None => fail(missing),
     }
+}
 
-    // std::fs::write("out.txt", grid.print(|v| v == &1));
-
-    println!("{:?}", start.elapsed());
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    exit(2);
 }
@@ -1,7 +1,7 @@
-use grid::grid3::*;
+// A scratch binary for the tiered grids. The helpers below main are kept around for experiments.
+
 use std::time::{Instant, Duration};
 use grid::grid4::{Grid4, Point, TieredGrid};
-use std::thread::Thread;
 use std::thread;
 
 use peak_alloc::PeakAlloc;
@@ -10,8 +10,6 @@ use peak_alloc::PeakAlloc;
 static PEAK_ALLOC: PeakAlloc = PeakAlloc;
 
 fn main() {
-    let g3: GridL3<u8, 3, 3, 3> = GridL3::init_none();
-
     // let start = Instant::now();
     // for i in 0..100_000_000 {
     //     ternary::<24>(i);
@@ -54,6 +52,23 @@ fn main() {
 
     println!("{:?}", start.elapsed());
 
+    let mut g4: Grid4<usize, 96, 128> = Grid4::new();
+
+    let start = Instant::now();
+
+    for x in 0..l {
+        for z in 0..l {
+            g4.set(&Point::new(x, z), 5);
+        }
+    }
+    for x in 0..l {
+        for z in 0..l {
+            g4.set(&Point::new(x, z), 5);
+        }
+    }
+
+    println!("{:?}", start.elapsed());
+
     thread::sleep(Duration::from_secs(1));
     println!("{:?}", PEAK_ALLOC.peak_usage_as_gb());
 
@@ -82,31 +97,9 @@ fn main() {
 
 
     // thread::sleep(Duration::from_secs(100));
-
-    return;
-
-    Index::new(0, 0).to_l3::<3, 3, 3>();
-    println!();
-
-    Index::new(2, 2).to_l3::<3, 3, 3>();
-    println!();
-
-    Index::new(3, 3).to_l3::<3, 3, 3>();
-    println!();
-
-    Index::new(5, 5).to_l3::<3, 3, 3>();
-    println!();
-
-    Index::new(6, 6).to_l3::<3, 3, 3>();
-    println!();
-
-    Index::new(8, 8).to_l3::<3, 3, 3>();
-    println!();
-
-    Index::new(9, 9).to_l3::<3, 3, 3>();
-    println!();
 }
 
+#[allow(dead_code)]
 fn index_3l<const L1: usize, const L2: usize, const L3: usize>(x: usize) -> (usize, usize, usize) {
     let mut x = x;
 
@@ -126,8 +119,9 @@ fn index_3l<const L1: usize, const L2: usize, const L3: usize>(x: usize) -> (usi
     (i1, i2, i3)
 }
 
+#[allow(dead_code)]
 fn ternary<const L: usize>(x: usize) -> [usize; L] {
-    let mut x = x.clone();
+    let mut x = x;
     let mut r: [usize; L] = [0; L];
 
     let mut i = L;
@@ -142,8 +136,9 @@ fn ternary<const L: usize>(x: usize) -> [usize; L] {
     r
 }
 
+#[allow(dead_code)]
 fn ternary_2<const L: usize>(x: usize, lengths: &[usize; L]) -> [usize; L] {
-    let mut x = x.clone();
+    let mut x = x;
     let mut r: [usize; L] = [0; L];
 
     for i in (0..L).rev() {
@@ -159,6 +154,7 @@ fn ternary_2<const L: usize>(x: usize, lengths: &[usize; L]) -> [usize; L] {
     r
 }
 
+#[allow(dead_code)]
 fn div_rem(n: usize, d: usize) -> (usize, usize) {
     let q = n / d;
     let r = n % d;
@@ -0,0 +1,609 @@
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use rustc_hash::FxHashMap;
+
+use crate::frozen::FrozenGrid;
+use crate::deflate::BitWriter;
+use crate::image::{Image, ImageFormat, ImageRenderer, Rgb};
+use crate::stats::TickReport;
+use crate::{Grid, GridError, Point, TickHook};
+
+/// The part of the grid each frame shows.
+#[derive(Clone, Debug)]
+pub enum Viewport {
+    /// The rectangle from `start` to `end`, inclusive.
+    Fixed { start: Point, end: Point },
+    /// Every cell that differs from `T::default()` in any frame, plus `margin` cells on each side.
+    /// Frames are kept as snapshots until `finish`, then all drawn at the final size. Snapshots
+    /// share unchanged chunks, but every chunk a generation writes stays in memory until `finish`,
+    /// so long or busy runs should use `Fixed`, which writes each frame as it is captured.
+    Auto { margin: isize },
+}
+
+/// Records frames of a run as an animated GIF or a numbered image sequence. Install
+/// [`FrameRecorder::hook`] on the grid to capture every `interval` generations as it ticks, then
+/// call [`FrameRecorder::finish`] once the run is over.
+pub struct FrameRecorder<T, const L: usize> where T: Default + Clone + Display {
+    inner: Arc<Mutex<Recording<T, L>>>,
+}
+
+enum Sink {
+    Gif(Box<dyn Write + Send>),
+    Sequence { dir: PathBuf, prefix: String, format: ImageFormat },
+}
+
+struct Recording<T, const L: usize> where T: Default + Clone + Display {
+    renderer: ImageRenderer<T>,
+    sink: Sink,
+    viewport: Viewport,
+    interval: u64,
+    delay: u16,
+    show_generation: bool,
+    show_population: bool,
+    // Grown by every frame under `Viewport::Auto`.
+    bounds: Option<(Point, Point)>,
+    pending: Vec<(FrozenGrid<T, L>, usize)>,
+    frames: usize,
+    error: Option<GridError>,
+    finished: bool,
+}
+
+impl<T, const L: usize> FrameRecorder<T, L> where T: Default + Clone + Display + PartialEq + Send + Sync + 'static {
+    /// An animated GIF that loops forever. Each frame carries its own colour table, so palettes of
+    /// up to 256 colours are exact; beyond that, further colours take the nearest one seen.
+    pub fn gif<W: Write + Send + 'static>(w: W, renderer: ImageRenderer<T>, viewport: Viewport) -> Self {
+        Self::new(Sink::Gif(Box::new(w)), renderer, viewport)
+    }
+
+    /// Files named `prefix` followed by the frame number, zero-padded to six digits, in `dir`,
+    /// which is created if needed.
+    pub fn sequence<P: Into<PathBuf>>(dir: P, prefix: &str, format: ImageFormat, renderer: ImageRenderer<T>, viewport: Viewport) -> Self {
+        Self::new(Sink::Sequence { dir: dir.into(), prefix: prefix.to_string(), format }, renderer, viewport)
+    }
+
+    fn new(sink: Sink, renderer: ImageRenderer<T>, viewport: Viewport) -> Self {
+        let recording = Recording {
+            renderer,
+            sink,
+            viewport,
+            interval: 1,
+            delay: 10,
+            show_generation: false,
+            show_population: false,
+            bounds: None,
+            pending: Vec::new(),
+            frames: 0,
+            error: None,
+            finished: false,
+        };
+
+        Self { inner: Arc::new(Mutex::new(recording)) }
+    }
+
+    /// Captures every `interval` generations; 0 captures only through `capture`.
+    pub fn with_interval(self, interval: u64) -> Self {
+        self.inner.lock().unwrap().interval = interval;
+        self
+    }
+
+    /// The time each GIF frame is shown, in hundredths of a second.
+    pub fn with_delay(self, centiseconds: u16) -> Self {
+        self.inner.lock().unwrap().delay = centiseconds;
+        self
+    }
+
+    pub fn with_generation_overlay(self) -> Self {
+        self.inner.lock().unwrap().show_generation = true;
+        self
+    }
+
+    pub fn with_population_overlay(self) -> Self {
+        self.inner.lock().unwrap().show_population = true;
+        self
+    }
+
+    /// A tick hook for `Grid::set_tick_hook` that captures the generations the interval selects.
+    /// Errors are kept and returned by `finish`, and stop the recording.
+    pub fn hook(&self) -> TickHook<T, L> {
+        let inner = self.inner.clone();
+
+        Box::new(move |grid, report: &TickReport| {
+            let mut recording = inner.lock().unwrap();
+            if recording.interval > 0 && report.generation % recording.interval == 0 {
+                recording.capture(grid, report.population);
+            }
+        })
+    }
+
+    /// Captures the grid as it is now, such as the starting state before the first tick.
+    pub fn capture(&self, grid: &Grid<T, L>) -> Result<(), GridError> {
+        let default = T::default();
+        let mut recording = self.inner.lock().unwrap();
+
+        recording.capture(grid, grid.population(|v| *v != default));
+        match &recording.error {
+            None => Ok(()),
+            Some(e) => Err(e.clone()),
+        }
+    }
+
+    /// The number of frames captured so far.
+    pub fn frames(&self) -> usize {
+        let recording = self.inner.lock().unwrap();
+        recording.frames + recording.pending.len()
+    }
+
+    /// Writes any frames still held and ends the file. Returns the number of frames written. A
+    /// hook still installed on a grid captures nothing afterwards.
+    pub fn finish(self) -> Result<usize, GridError> {
+        let mut guard = self.inner.lock().unwrap();
+        let recording = &mut *guard;
+        recording.finished = true;
+
+        if let Some(e) = recording.error.take() {
+            return Err(e);
+        }
+
+        let (start, end) = recording.viewport_bounds();
+        for (grid, population) in std::mem::take(&mut recording.pending) {
+            let image = recording.renderer.render(&grid, &start, &end)?;
+            recording.emit(image, grid.generation(), population)?;
+        }
+
+        if let Sink::Gif(w) = &mut recording.sink {
+            if recording.frames == 0 {
+                return Err(GridError::InvalidArgument("cannot write a gif without frames".to_string()));
+            }
+
+            w.write_all(&[0x3B])?;
+            w.flush()?;
+        }
+
+        Ok(recording.frames)
+    }
+}
+
+impl<T, const L: usize> Recording<T, L> where T: Default + Clone + Display + PartialEq {
+    fn capture(&mut self, grid: &Grid<T, L>, population: usize) {
+        if self.finished || self.error.is_some() {
+            return;
+        }
+
+        let r = match self.viewport.clone() {
+            Viewport::Fixed { start, end } => self.renderer.render(grid, &start, &end)
+                .and_then(|image| self.emit(image, grid.generation(), population)),
+            Viewport::Auto { margin } => {
+                if let Some((min, max)) = grid.bounding_box() {
+                    let (min, max) = (Point::new(min.x - margin, min.z - margin), Point::new(max.x + margin, max.z + margin));
+                    self.bounds = Some(match self.bounds.take() {
+                        None => (min, max),
+                        Some((a, b)) => (Point::new(a.x.min(min.x), a.z.min(min.z)), Point::new(b.x.max(max.x), b.z.max(max.z))),
+                    });
+                }
+
+                self.pending.push((grid.snapshot(), population));
+                Ok(())
+            }
+        };
+
+        if let Err(e) = r {
+            self.error = Some(e);
+        }
+    }
+
+    // An empty auto viewport shows the margin around the origin.
+    fn viewport_bounds(&self) -> (Point, Point) {
+        match (&self.viewport, &self.bounds) {
+            (Viewport::Fixed { start, end }, _) => (start.copy(), end.copy()),
+            (Viewport::Auto { .. }, Some((min, max))) => (min.copy(), max.copy()),
+            (Viewport::Auto { margin }, None) => (Point::new(-margin, -margin), Point::new(*margin, *margin)),
+        }
+    }
+
+    fn emit(&mut self, mut image: Image, generation: u64, population: usize) -> Result<(), GridError> {
+        let mut text = Vec::new();
+        if self.show_generation {
+            text.push(format!("GEN {}", generation));
+        }
+        if self.show_population {
+            text.push(format!("POP {}", population));
+        }
+        if !text.is_empty() {
+            draw_text(&mut image, &text.join("  "));
+        }
+
+        match &mut self.sink {
+            Sink::Gif(w) => {
+                if self.frames == 0 {
+                    write_gif_header(w, &image)?;
+                }
+                write_gif_frame(w, &image, self.delay)?;
+            }
+            Sink::Sequence { dir, prefix, format } => {
+                if self.frames == 0 {
+                    fs::create_dir_all(&dir)?;
+                }
+
+                let path = dir.join(format!("{}{:06}.{}", prefix, self.frames, format.extension()));
+                image.write(BufWriter::new(File::create(path)?), *format)?;
+            }
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+//
+
+// 3x5 glyphs, one row per three bits, most significant bit on the left.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' | 'O' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'G' => [0b111, 0b100, 0b101, 0b101, 0b111],
+        'E' => [0b111, 0b100, 0b111, 0b100, 0b111],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'P' => [0b111, 0b101, 0b111, 0b100, 0b100],
+        _ => [0; 5],
+    }
+}
+
+// White text on a black box in the top left corner, scaled up for larger images.
+fn draw_text(image: &mut Image, text: &str) {
+    let scale = (image.width() / 160).clamp(1, 4);
+    let (x0, y0) = (scale, scale);
+
+    let width = text.chars().count() * 4 * scale + scale;
+    image.fill_rect(0, 0, x0 + width, y0 + 7 * scale, Rgb::BLACK);
+
+    for (i, c) in text.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..3 {
+                if bits >> (2 - col) & 1 == 1 {
+                    image.fill_rect(x0 + scale + (i * 4 + col) * scale, y0 + scale + row * scale, scale, scale, Rgb::WHITE);
+                }
+            }
+        }
+    }
+}
+
+fn write_gif_header<W: Write + ?Sized>(w: &mut W, image: &Image) -> Result<(), GridError> {
+    if image.width() > u16::MAX as usize || image.height() > u16::MAX as usize {
+        return Err(GridError::InvalidArgument(format!("a {}x{} frame is too large for a gif", image.width(), image.height())));
+    }
+
+    w.write_all(b"GIF89a")?;
+    w.write_all(&(image.width() as u16).to_le_bytes())?;
+    w.write_all(&(image.height() as u16).to_le_bytes())?;
+    // No global colour table, background colour 0, square pixels.
+    w.write_all(&[0, 0, 0])?;
+
+    // Loop forever.
+    w.write_all(&[0x21, 0xFF, 0x0B])?;
+    w.write_all(b"NETSCAPE2.0")?;
+    w.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+    Ok(())
+}
+
+fn write_gif_frame<W: Write + ?Sized>(w: &mut W, image: &Image, delay: u16) -> Result<(), GridError> {
+    let mut table: Vec<Rgb> = Vec::new();
+    let mut lookup: FxHashMap<Rgb, u8> = FxHashMap::default();
+
+    let indices: Vec<u8> = image.pixels().iter().map(|c| {
+        if let Some(i) = lookup.get(c) {
+            return *i;
+        }
+
+        let i = match table.len() {
+            n if n < 256 => {
+                table.push(*c);
+                n as u8
+            }
+            _ => nearest(&table, c),
+        };
+        lookup.insert(*c, i);
+        i
+    }).collect();
+
+    let bits = (usize::BITS - (table.len().max(2) - 1).leading_zeros()) as u8;
+
+    // Graphic control extension: no disposal, no transparency.
+    w.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+    w.write_all(&delay.to_le_bytes())?;
+    w.write_all(&[0x00, 0x00])?;
+
+    // Image descriptor with a local colour table.
+    w.write_all(&[0x2C, 0, 0, 0, 0])?;
+    w.write_all(&(image.width() as u16).to_le_bytes())?;
+    w.write_all(&(image.height() as u16).to_le_bytes())?;
+    w.write_all(&[0x80 | (bits - 1)])?;
+
+    for i in 0..1 << bits {
+        let c = table.get(i).copied().unwrap_or(Rgb::BLACK);
+        w.write_all(&[c.0, c.1, c.2])?;
+    }
+
+    let min_code_size = bits.max(2);
+    w.write_all(&[min_code_size])?;
+    for block in lzw(&indices, min_code_size).chunks(255) {
+        w.write_all(&[block.len() as u8])?;
+        w.write_all(block)?;
+    }
+    w.write_all(&[0x00])?;
+
+    Ok(())
+}
+
+fn nearest(table: &[Rgb], c: &Rgb) -> u8 {
+    let d = |a: &Rgb| {
+        let (r, g, b) = (a.0 as i32 - c.0 as i32, a.1 as i32 - c.1 as i32, a.2 as i32 - c.2 as i32);
+        r * r + g * g + b * b
+    };
+
+    (0..table.len()).min_by_key(|i| d(&table[*i])).unwrap() as u8
+}
+
+// Variable-width LZW as GIF uses it: codes grow from `min_code_size + 1` bits up to 12, and the
+// table is cleared once full.
+fn lzw(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u32 << min_code_size;
+    let end = clear + 1;
+
+    let mut out = BitWriter::new();
+    let mut table: FxHashMap<(u32, u8), u32> = FxHashMap::default();
+    let mut next = end + 1;
+    let mut width = min_code_size as u32 + 1;
+
+    out.bits(clear, width);
+
+    let mut indices = indices.iter();
+    let mut prefix = match indices.next() {
+        None => {
+            out.bits(end, width);
+            out.flush();
+            return out.bytes;
+        }
+        Some(i) => *i as u32,
+    };
+
+    for k in indices {
+        if let Some(code) = table.get(&(prefix, *k)) {
+            prefix = *code;
+            continue;
+        }
+
+        out.bits(prefix, width);
+
+        if next < 4096 {
+            table.insert((prefix, *k), next);
+            next += 1;
+            if next > 1 << width && width < 12 {
+                width += 1;
+            }
+        } else {
+            out.bits(clear, width);
+            table.clear();
+            next = end + 1;
+            width = min_code_size as u32 + 1;
+        }
+
+        prefix = *k as u32;
+    }
+
+    out.bits(prefix, width);
+    out.bits(end, width);
+    out.flush();
+
+    out.bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+
+    // A GIF decoder covering what `write_gif_frame` produces: local colour tables and LZW data,
+    // with extensions skipped. Returns each frame's pixels.
+    fn decode_gif(data: &[u8]) -> (usize, usize, Vec<Vec<Rgb>>) {
+        assert_eq!(&data[..6], b"GIF89a");
+        let width = u16::from_le_bytes([data[6], data[7]]) as usize;
+        let height = u16::from_le_bytes([data[8], data[9]]) as usize;
+        assert_eq!(data[10] & 0x80, 0, "no global colour table");
+
+        let mut frames = Vec::new();
+        let mut pos = 13;
+        loop {
+            match data[pos] {
+                0x3B => return (width, height, frames),
+                0x21 => {
+                    pos += 2;
+                    pos = skip_blocks(data, pos);
+                }
+                0x2C => {
+                    let w = u16::from_le_bytes([data[pos + 5], data[pos + 6]]) as usize;
+                    let h = u16::from_le_bytes([data[pos + 7], data[pos + 8]]) as usize;
+                    assert_eq!((w, h), (width, height));
+
+                    let flags = data[pos + 9];
+                    assert_eq!(flags & 0x80, 0x80, "local colour table");
+                    pos += 10;
+
+                    let colours = 1 << ((flags & 7) + 1);
+                    let table: Vec<Rgb> = (0..colours).map(|i| Rgb(data[pos + i * 3], data[pos + i * 3 + 1], data[pos + i * 3 + 2])).collect();
+                    pos += colours * 3;
+
+                    let min_code_size = data[pos];
+                    let end = skip_blocks(data, pos + 1);
+                    let mut lzw_data = Vec::new();
+                    let mut i = pos + 1;
+                    while data[i] != 0 {
+                        lzw_data.extend_from_slice(&data[i + 1..i + 1 + data[i] as usize]);
+                        i += 1 + data[i] as usize;
+                    }
+                    pos = end;
+
+                    let indices = unlzw(&lzw_data, min_code_size);
+                    assert_eq!(indices.len(), w * h);
+                    frames.push(indices.iter().map(|i| table[*i as usize]).collect());
+                }
+                b => panic!("unexpected block {:02x}", b),
+            }
+        }
+    }
+
+    fn skip_blocks(data: &[u8], mut pos: usize) -> usize {
+        while data[pos] != 0 {
+            pos += 1 + data[pos] as usize;
+        }
+        pos + 1
+    }
+
+    // Decodes GIF's LZW the way viewers do: the code width grows once the next free code needs
+    // another bit, and a full table waits for the encoder's clear code.
+    fn unlzw(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1u32 << min_code_size;
+        let end = clear + 1;
+        let reset = || -> Vec<Vec<u8>> { (0..clear).map(|i| vec![i as u8]).chain([vec![], vec![]]).collect() };
+
+        let mut table = reset();
+        let mut width = min_code_size as u32 + 1;
+        let mut prev: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+
+        let mut bit = 0;
+        let mut read = |width: u32| -> u32 {
+            let mut v = 0;
+            for i in 0..width {
+                let byte = data[bit / 8];
+                v |= ((byte >> (bit % 8)) as u32 & 1) << i;
+                bit += 1;
+            }
+            v
+        };
+
+        loop {
+            let code = read(width);
+            if code == clear {
+                table = reset();
+                width = min_code_size as u32 + 1;
+                prev = None;
+                continue;
+            }
+            if code == end {
+                return out;
+            }
+
+            let entry = match (table.get(code as usize), &prev) {
+                (Some(e), _) => e.clone(),
+                (None, Some(p)) if code as usize == table.len() => {
+                    let mut e = p.clone();
+                    e.push(p[0]);
+                    e
+                }
+                _ => panic!("code {} is not in the table", code),
+            };
+
+            if let Some(p) = prev {
+                if table.len() < 4096 {
+                    let mut e = p;
+                    e.push(entry[0]);
+                    table.push(e);
+                    if table.len() == 1 << width && width < 12 {
+                        width += 1;
+                    }
+                }
+            }
+
+            out.extend_from_slice(&entry);
+            prev = Some(entry);
+        }
+    }
+
+    fn noise(len: usize, colours: u32, mut seed: u32) -> Vec<u8> {
+        (0..len).map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            (seed % colours) as u8
+        }).collect()
+    }
+
+    #[test]
+    fn lzw_round_trips() {
+        let cases: Vec<(Vec<u8>, u8)> = vec![
+            (vec![], 2),
+            (vec![1], 2),
+            (vec![0; 10000], 2),
+            (noise(5000, 2, 1), 2),
+            (noise(5000, 4, 2), 2),
+            (noise(20000, 16, 3), 4),
+            // Enough distinct strings to fill the table several times over.
+            (noise(100000, 256, 4), 8),
+        ];
+
+        for (indices, min_code_size) in cases {
+            assert_eq!(unlzw(&lzw(&indices, min_code_size), min_code_size), indices, "{} indices", indices.len());
+        }
+    }
+
+    // Collects what the recorder writes, since the sink must be owned.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn gif_frames_decode_to_the_rendered_images() {
+        let palette = |v: &u8| match v {
+            0 => Rgb::BLACK,
+            1 => Rgb::WHITE,
+            v => Rgb(*v, 255 - *v, 7),
+        };
+
+        let mut grid: Grid<u8, 8> = Grid::new();
+        let (start, end) = (Point::new(-3, -2), Point::new(9, 5));
+        let out = Shared::default();
+        let frames = FrameRecorder::gif(out.clone(), ImageRenderer::new(palette), Viewport::Fixed { start: start.copy(), end: end.copy() })
+            .with_interval(0);
+
+        let mut expected = Vec::new();
+        for i in 0..4u8 {
+            for x in 0..=i as isize {
+                grid.set(&Point::new(x * 2 - 3, x - 2), 40 * i + x as u8);
+            }
+            frames.capture(&grid).unwrap();
+            expected.push(ImageRenderer::new(palette).render(&grid, &start, &end).unwrap());
+        }
+        assert_eq!(frames.finish().unwrap(), 4);
+
+        let data = out.0.lock().unwrap().clone();
+        let (width, height, decoded) = decode_gif(&data);
+
+        assert_eq!((width, height), (13, 8));
+        let expected: Vec<Vec<Rgb>> = expected.iter().map(|image: &Image| image.pixels().to_vec()).collect();
+        assert_eq!(decoded, expected);
+    }
+}
@@ -0,0 +1,415 @@
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::cycle::CycleDetector;
+use crate::pattern::Transform;
+use crate::rule::Rule;
+use crate::{Grid, Point};
+
+/// What an object does when run on its own.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Class {
+    StillLife,
+    Oscillator { period: u64 },
+    /// Moves `dx` cells along one axis and `dz` along the other every `period` generations, with
+    /// `dx >= dz >= 0` so that every orientation of a ship reports the same speed.
+    Spaceship { period: u64, dx: isize, dz: isize },
+    /// Dies out when isolated, so it was only stable through its neighbours.
+    Dies,
+    /// Found no cycle within the period limit.
+    Unclassified,
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Class::StillLife => write!(f, "still life"),
+            Class::Oscillator { period } => write!(f, "p{} oscillator", period),
+            Class::Spaceship { period, dx, dz } => {
+                match dx {
+                    1 => write!(f, "c/{}", period)?,
+                    _ => write!(f, "{}c/{}", dx, period)?,
+                }
+                match dz {
+                    0 => write!(f, " orthogonal spaceship"),
+                    _ if dz == dx => write!(f, " diagonal spaceship"),
+                    _ => write!(f, " ({}, {}) spaceship", dx, dz),
+                }
+            }
+            Class::Dies => write!(f, "dies"),
+            Class::Unclassified => write!(f, "unclassified"),
+        }
+    }
+}
+
+/// A connected group of live cells and its classification.
+#[derive(Clone, Debug)]
+pub struct Object {
+    /// Live cells in canonical form: the phase and orientation that `code` encodes, translated so
+    /// its bounding box starts at the origin. Any two copies of an object, in any phase, rotation
+    /// or reflection, share it. Cell states are dropped, so only two-state objects are told apart.
+    pub cells: Vec<Point>,
+    pub class: Class,
+    /// The object's apgcode, as Catagolue names it, such as `xs4_33` for the block: `xs<cells>`
+    /// for still lives, `xp<period>` for oscillators and `xq<period>` for spaceships, then the
+    /// extended Wechsler encoding of whichever phase and orientation gives the shortest code,
+    /// ties going to the least. Objects that die or were not classified get `zz_` and the same
+    /// encoding of their first phase, which is not a Catagolue name.
+    pub code: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct CensusEntry {
+    pub code: String,
+    pub class: Class,
+    pub count: usize,
+}
+
+/// How many of each object a set of grids contained.
+#[derive(Clone, Debug, Default)]
+pub struct Census {
+    entries: FxHashMap<String, CensusEntry>,
+}
+
+impl Census {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, object: &Object) {
+        self.entries.entry(object.code.clone())
+            .or_insert_with(|| CensusEntry { code: object.code.clone(), class: object.class, count: 0 })
+            .count += 1;
+    }
+
+    /// Adds the counts of another census, such as one from another soup.
+    pub fn merge(&mut self, other: &Census) {
+        for entry in other.entries.values() {
+            self.entries.entry(entry.code.clone())
+                .or_insert_with(|| CensusEntry { count: 0, ..entry.clone() })
+                .count += entry.count;
+        }
+    }
+
+    /// Entries from most to least common.
+    pub fn entries(&self) -> Vec<&CensusEntry> {
+        let mut r: Vec<&CensusEntry> = self.entries.values().collect();
+        r.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.code.cmp(&b.code)));
+        r
+    }
+
+    pub fn total(&self) -> usize {
+        self.entries.values().map(|e| e.count).sum()
+    }
+}
+
+impl Display for Census {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let entries = self.entries();
+        let width = entries.iter().map(|e| e.code.len()).max().unwrap_or(0).max(4);
+
+        writeln!(f, "{:>8}  {:<width$}  class", "count", "code", width = width)?;
+        for e in entries {
+            writeln!(f, "{:>8}  {:<width$}  {}", e.count, e.code, e.class, width = width)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display + PartialEq + Hash {
+    /// Splits the live cells into groups connected through the Moore neighbourhood.
+    pub fn objects(&self) -> Vec<Vec<(Point, T)>> {
+        let default = T::default();
+        let cells: FxHashMap<Point, T> = self.iter()
+            .filter(|(_, v)| **v != default)
+            .map(|(p, v)| (p, v.clone()))
+            .collect();
+        let mut seen = FxHashSet::default();
+        let mut r = Vec::new();
+
+        for p in cells.keys() {
+            if !seen.insert(p.copy()) {
+                continue;
+            }
+
+            let mut object = Vec::new();
+            let mut stack = vec![p.copy()];
+
+            while let Some(p) = stack.pop() {
+                for n in p.moore_neighbors(1, false) {
+                    if cells.contains_key(&n) && seen.insert(n.copy()) {
+                        stack.push(n);
+                    }
+                }
+
+                let v = cells[&p].clone();
+                object.push((p, v));
+            }
+
+            r.push(object);
+        }
+
+        r
+    }
+
+    /// Separates the grid into objects and classifies each under `rule`, looking for cycles of up
+    /// to `max_period` generations. Meant for soups that have settled; objects still interacting
+    /// with each other come out as `Dies` or `Unclassified`.
+    pub fn census<R: Rule<T>>(&self, rule: &R, max_period: u64) -> Census {
+        let mut census = Census::new();
+
+        for object in self.objects() {
+            census.add(&classify::<T, R, L>(&object, rule, max_period));
+        }
+
+        census
+    }
+}
+
+/// Runs `cells` on an otherwise empty grid until it repeats, up to a translation, within
+/// `max_period` generations.
+pub fn classify<T, R, const L: usize>(cells: &[(Point, T)], rule: &R, max_period: u64) -> Object
+    where T: Default + Clone + Display + PartialEq + Hash,
+          R: Rule<T>,
+{
+    let default = T::default();
+    let mut grid: Grid<T, L> = Grid::new();
+    for (p, v) in cells {
+        grid.set(p, v.clone());
+    }
+
+    let mut detector = CycleDetector::new(max_period);
+    let mut phases = vec![cells.iter().map(|(p, _)| p.copy()).collect::<Vec<_>>()];
+    detector.observe(&mut grid);
+
+    for _ in 0..max_period {
+        let report = grid.step(rule);
+        if report.population == 0 {
+            return object(&phases[..1], Class::Dies);
+        }
+
+        phases.push(grid.iter().filter(|(_, v)| **v != default).map(|(p, _)| p).collect());
+
+        if let Some(cycle) = detector.observe(&mut grid) {
+            let (dx, dz) = (cycle.dx.abs(), cycle.dz.abs());
+
+            let class = match (cycle.period, cycle.is_spaceship()) {
+                (_, true) => Class::Spaceship { period: cycle.period, dx: dx.max(dz), dz: dx.min(dz) },
+                (1, false) => Class::StillLife,
+                (period, false) => Class::Oscillator { period },
+            };
+
+            return object(&phases[phases.len() - cycle.period as usize..], class);
+        }
+    }
+
+    object(&phases[..1], Class::Unclassified)
+}
+
+fn object(phases: &[Vec<Point>], class: Class) -> Object {
+    let (wechsler, cells) = phases.iter()
+        .flat_map(|cells| orientations(cells))
+        .map(|cells| (encode(&cells), cells))
+        .min_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+        .unwrap_or_default();
+
+    let prefix = match class {
+        Class::StillLife => format!("xs{}", cells.len()),
+        Class::Oscillator { period } => format!("xp{}", period),
+        Class::Spaceship { period, .. } => format!("xq{}", period),
+        Class::Dies | Class::Unclassified => "zz".to_string(),
+    };
+
+    let code = format!("{}_{}", prefix, wechsler);
+
+    Object {
+        cells: cells.into_iter().map(|(x, z)| Point::new(x, z)).collect(),
+        class,
+        code,
+    }
+}
+
+// The eight rotations and reflections of `cells`, each translated to the origin and sorted.
+fn orientations(cells: &[Point]) -> Vec<Vec<(isize, isize)>> {
+    Transform::ALL.iter().map(|t| {
+        let mut r: Vec<(isize, isize)> = cells.iter().map(|p| t.apply(p.x, p.z)).collect();
+
+        let min_x = r.iter().map(|(x, _)| *x).min().unwrap_or(0);
+        let min_z = r.iter().map(|(_, z)| *z).min().unwrap_or(0);
+        for (x, z) in &mut r {
+            *x -= min_x;
+            *z -= min_z;
+        }
+
+        r.sort_unstable_by_key(|(x, z)| (*z, *x));
+        r
+    }).collect()
+}
+
+// Extended Wechsler format: the rows are cut into strips 5 high, separated by 'z', and each column
+// of a strip is a base-32 digit with the top row as its lowest bit. Zeros at the end of a strip are
+// dropped, and runs of them inside one are written as 'w' for 2, 'x' for 3 and 'y' followed by a
+// digit for 4 to 39.
+fn encode(cells: &[(isize, isize)]) -> String {
+    const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    let height = cells.iter().map(|(_, z)| *z + 1).max().unwrap_or(0) as usize;
+    let width = cells.iter().map(|(x, _)| *x + 1).max().unwrap_or(0) as usize;
+
+    let mut strips = vec![vec![0u8; width]; height.div_ceil(5)];
+    for (x, z) in cells {
+        strips[*z as usize / 5][*x as usize] |= 1 << (*z as usize % 5);
+    }
+
+    let mut r = String::new();
+    for (i, strip) in strips.iter().enumerate() {
+        if i > 0 {
+            r.push('z');
+        }
+
+        let mut zeros = 0;
+        for column in strip {
+            if *column == 0 {
+                zeros += 1;
+                continue;
+            }
+
+            while zeros > 0 {
+                match zeros {
+                    1 => r.push('0'),
+                    2 => r.push('w'),
+                    3 => r.push('x'),
+                    _ => {
+                        let n = zeros.min(39);
+                        r.push('y');
+                        r.push(DIGITS[n - 4] as char);
+                        zeros -= n;
+                        continue;
+                    }
+                }
+                zeros = 0;
+            }
+
+            r.push(DIGITS[*column as usize] as char);
+        }
+    }
+
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::LifeRule;
+
+    const BLOCK: &[(isize, isize)] = &[(0, 0), (1, 0), (0, 1), (1, 1)];
+    const BLINKER: &[(isize, isize)] = &[(0, 0), (1, 0), (2, 0)];
+    const GLIDER: &[(isize, isize)] = &[(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+
+    fn grid(cells: &[(isize, isize)], dx: isize, dz: isize) -> Grid<u8, 8> {
+        let mut grid = Grid::new();
+        for (x, z) in cells {
+            grid.set(&Point::new(x + dx, z + dz), 1);
+        }
+        grid
+    }
+
+    fn live(grid: &Grid<u8, 8>) -> Vec<(Point, u8)> {
+        grid.iter().filter(|(_, v)| **v != 0).map(|(p, v)| (p, *v)).collect()
+    }
+
+    // The object in every rotation, reflection and phase up to `phases`.
+    fn classify_all(cells: &[(isize, isize)], phases: usize) -> Vec<Object> {
+        let rule = LifeRule::conway();
+        let mut r = Vec::new();
+
+        for t in Transform::ALL.iter() {
+            let moved: Vec<(isize, isize)> = cells.iter().map(|(x, z)| t.apply(*x, *z)).collect();
+            let mut grid = grid(&moved, 20, -7);
+
+            for _ in 0..phases {
+                r.push(classify::<u8, _, 8>(&live(&grid), &rule, 8));
+                grid.step(&rule);
+            }
+        }
+
+        r
+    }
+
+    #[test]
+    fn codes_match_catagolue() {
+        for (cells, phases, class, code) in [
+            (BLOCK, 1, Class::StillLife, "xs4_33"),
+            (BLINKER, 2, Class::Oscillator { period: 2 }, "xp2_7"),
+            (GLIDER, 4, Class::Spaceship { period: 4, dx: 1, dz: 1 }, "xq4_153"),
+        ] {
+            let objects = classify_all(cells, phases);
+            assert_eq!(objects.len(), 8 * phases);
+
+            for object in &objects {
+                assert_eq!(object.class, class);
+                assert_eq!(object.code, code);
+                assert_eq!(object.cells, objects[0].cells);
+            }
+        }
+
+        let rule = LifeRule::conway();
+        let beehive = [(1, 0), (2, 0), (0, 1), (3, 1), (1, 2), (2, 2)];
+        let lwss = [(1, 0), (4, 0), (0, 1), (0, 2), (4, 2), (0, 3), (1, 3), (2, 3), (3, 3)];
+        assert_eq!(classify::<u8, _, 8>(&live(&grid(&beehive, 0, 0)), &rule, 8).code, "xs6_696");
+        assert_eq!(classify::<u8, _, 8>(&live(&grid(&lwss, 0, 0)), &rule, 8).code, "xq4_6frc");
+    }
+
+    #[test]
+    fn encode_compresses_zeros_and_splits_strips() {
+        assert_eq!(encode(&[(0, 0), (3, 0)]), "1w1");
+        assert_eq!(encode(&[(0, 0), (4, 0)]), "1x1");
+        assert_eq!(encode(&[(0, 0), (5, 0)]), "1y01");
+        // 40 zeros take a full 'y' run of 39 and a single one.
+        assert_eq!(encode(&[(0, 0), (41, 0)]), "1yz01");
+        // The empty column ending the first strip is dropped.
+        assert_eq!(encode(&[(0, 0), (1, 5)]), "1z01");
+        assert_eq!(encode(&[(0, 0), (0, 10)]), "1zz1");
+    }
+
+    #[test]
+    fn objects_are_split_by_moore_connectivity() {
+        // Two blocks one column apart are separate; a cell touching a corner joins its block.
+        let mut g = grid(BLOCK, 0, 0);
+        for (x, z) in BLOCK {
+            g.set(&Point::new(x + 3, *z), 1);
+        }
+        assert_eq!(g.objects().len(), 2);
+
+        g.set(&Point::new(-1, -1), 1);
+        let mut sizes: Vec<usize> = g.objects().iter().map(|o| o.len()).collect();
+        sizes.sort_unstable();
+        assert_eq!(sizes, vec![4, 5]);
+    }
+
+    #[test]
+    fn census_counts_and_merges() {
+        let rule = LifeRule::conway();
+        let mut g = grid(BLOCK, 0, 0);
+        for (x, z) in BLOCK {
+            g.set(&Point::new(x + 10, *z), 1);
+        }
+        for (x, z) in BLINKER {
+            g.set(&Point::new(x - 10, z + 10), 1);
+        }
+
+        let mut census = g.census(&rule, 8);
+        assert_eq!(census.total(), 3);
+        let counts: Vec<(&str, usize)> = census.entries().iter().map(|e| (e.code.as_str(), e.count)).collect();
+        assert_eq!(counts, vec![("xs4_33", 2), ("xp2_7", 1)]);
+
+        census.merge(&grid(GLIDER, 0, 0).census(&rule, 8));
+        census.merge(&g.census(&rule, 8));
+        let counts: Vec<(&str, usize)> = census.entries().iter().map(|e| (e.code.as_str(), e.count)).collect();
+        assert_eq!(counts, vec![("xs4_33", 4), ("xp2_7", 2), ("xq4_153", 1)]);
+        assert_eq!(census.total(), 7);
+    }
+}
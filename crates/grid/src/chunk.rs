@@ -0,0 +1,92 @@
+use std::ops::{Index, IndexMut};
+
+/// A square `L`×`L` block of values stored row-major in a single heap allocation. Indexing by `x`
+/// yields the row slice, so `chunk[x][z]` reads the same as the nested arrays it replaces.
+#[derive(Clone)]
+pub struct Chunk<T, const L: usize> {
+    values: Box<[T]>,
+}
+
+impl<T, const L: usize> Chunk<T, L> {
+    pub const LEN: usize = L * L;
+
+    pub fn from_fn<F: FnMut(usize, usize) -> T>(mut f: F) -> Self {
+        let mut values = Vec::with_capacity(Self::LEN);
+
+        for x in 0..L {
+            for z in 0..L {
+                values.push(f(x, z));
+            }
+        }
+
+        Self {
+            values: values.into_boxed_slice(),
+        }
+    }
+
+    pub fn get(&self, x: usize, z: usize) -> Option<&T> {
+        if x >= L || z >= L {
+            return None;
+        }
+
+        self.values.get(x * L + z)
+    }
+
+    pub fn get_mut(&mut self, x: usize, z: usize) -> Option<&mut T> {
+        if x >= L || z >= L {
+            return None;
+        }
+
+        self.values.get_mut(x * L + z)
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item=&[T]> + '_ {
+        (0..L).map(move |x| &self[x])
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item=&mut [T]> + '_ {
+        self.values.chunks_mut(L.max(1))
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+}
+
+impl<T, const L: usize> Chunk<T, L> where T: Default {
+    pub fn new() -> Self {
+        Self::from_fn(|_, _| T::default())
+    }
+}
+
+impl<T, const L: usize> Chunk<T, L> where T: Clone {
+    pub fn filled(v: T) -> Self {
+        Self {
+            values: vec![v; Self::LEN].into_boxed_slice(),
+        }
+    }
+}
+
+impl<T, const L: usize> Default for Chunk<T, L> where T: Default {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const L: usize> Index<usize> for Chunk<T, L> {
+    type Output = [T];
+
+    fn index(&self, x: usize) -> &[T] {
+        &self.values[x * L..(x + 1) * L]
+    }
+}
+
+impl<T, const L: usize> IndexMut<usize> for Chunk<T, L> {
+    fn index_mut(&mut self, x: usize) -> &mut [T] {
+        &mut self.values[x * L..(x + 1) * L]
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+
+use rustc_hash::FxHasher;
+
+use crate::chunk::Chunk;
+use crate::generator::mix64;
+use crate::Grid;
+
+// The grid hash is the polynomial sum of h(v) * A^x * B^z over live cells, modulo a Mersenne prime.
+// Each subgrid's share is cached in local coordinates, so after a tick only the subgrids that were
+// written to are rehashed. Translating a pattern by (dx, dz) multiplies the sum by A^dx * B^dz,
+// which dividing through by the powers at the bounding box corner cancels out.
+const P: u64 = (1 << 61) - 1;
+const A: u64 = 0x1F3D_5B79_A2C4_E681 % P;
+const B: u64 = 0x0C8E_4A26_F1D3_B597 % P;
+// Inverses for negative powers, base^(P - 2) by Fermat.
+const A_INV: u64 = pow(A, P - 2);
+const B_INV: u64 = pow(B, P - 2);
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SubGridHash {
+    hash: u64,
+    // Local corner of the live cells' bounding box, or None if there are none.
+    min: Option<(usize, usize)>,
+}
+
+/// A hash of the grid's live cells, together with where they are.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct CycleHash {
+    /// Equal for any two grids whose live cells differ only by a translation.
+    pub hash: u64,
+    /// Equal only for grids whose live cells are identical.
+    pub positional: u64,
+    /// The minimum corner of the live cells' bounding box, or None for an empty grid.
+    pub origin: Option<(isize, isize)>,
+}
+
+/// A cycle found by a `CycleDetector`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Cycle {
+    pub period: u64,
+    /// The first generation of the cycle.
+    pub generation: u64,
+    /// How far the pattern moves each period.
+    pub dx: isize,
+    pub dz: isize,
+}
+
+impl Cycle {
+    pub fn is_still_life(&self) -> bool {
+        self.period == 1 && !self.is_spaceship()
+    }
+
+    pub fn is_spaceship(&self) -> bool {
+        self.dx != 0 || self.dz != 0
+    }
+}
+
+/// Remembers the hashes of recent generations and reports when the grid repeats one, up to a
+/// translation.
+pub struct CycleDetector {
+    max_period: u64,
+    history: VecDeque<(u64, CycleHash)>,
+}
+
+impl CycleDetector {
+    /// Detects cycles with periods of at most `max_period` generations.
+    pub fn new(max_period: u64) -> Self {
+        Self {
+            max_period,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Records the grid's current generation. Returns the cycle it completes, if any, using the
+    /// shortest period that matches. Call once per generation, or periods will be misreported. If
+    /// the grid's generation has not moved forward since the last call, as after a rewind, the
+    /// generations seen so far are forgotten.
+    pub fn observe<T, const L: usize>(&mut self, grid: &mut Grid<T, L>) -> Option<Cycle>
+        where T: Default + Clone + Display + PartialEq + Hash
+    {
+        let generation = grid.generation();
+        let current = grid.cycle_hash();
+
+        if self.history.back().is_some_and(|(g, _)| *g >= generation) {
+            self.history.clear();
+        }
+
+        let cycle = self.history.iter().rev()
+            .find(|(_, h)| h.hash == current.hash)
+            .map(|(g, h)| {
+                let (dx, dz) = match (current.origin, h.origin) {
+                    (Some((x1, z1)), Some((x0, z0))) => (x1 - x0, z1 - z0),
+                    _ => (0, 0),
+                };
+
+                Cycle { period: generation - g, generation: *g, dx, dz }
+            });
+
+        self.history.push_back((generation, current));
+        while self.history.front().is_some_and(|(g, _)| generation - g > self.max_period) {
+            self.history.pop_front();
+        }
+
+        cycle
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display + PartialEq + Hash {
+    /// Hashes the live cells, rehashing only the subgrids written to since the last call.
+    pub fn cycle_hash(&mut self) -> CycleHash {
+        let (a_pow, b_pow) = (powers(A, L), powers(B, L));
+
+        for sub in self.values.values_mut() {
+            if sub.hash.is_none() {
+                sub.hash = Some(hash_subgrid(&sub.values, &a_pow, &b_pow));
+            }
+        }
+
+        self.combine_hashes(&a_pow, &b_pow)
+    }
+
+    /// The same hash as `cycle_hash`, for when the grid cannot be borrowed mutably. Subgrids that
+    /// are not cached already are hashed without caching them.
+    pub fn cycle_hash_uncached(&self) -> CycleHash {
+        self.combine_hashes(&powers(A, L), &powers(B, L))
+    }
+
+    fn combine_hashes(&self, a_pow: &[u64], b_pow: &[u64]) -> CycleHash {
+        let mut positional = 0;
+        let mut origin: Option<(isize, isize)> = None;
+
+        for (sub_index, sub) in &self.values {
+            let sub_hash = sub.hash.unwrap_or_else(|| hash_subgrid(&sub.values, a_pow, b_pow));
+
+            let (mx, mz) = match sub_hash.min {
+                Some(min) => min,
+                None => continue,
+            };
+
+            let x0 = sub_index.x * Grid::<T, L>::L_I;
+            let z0 = sub_index.z * Grid::<T, L>::L_I;
+
+            positional = add(positional, mul(sub_hash.hash, mul(pow_signed(A, A_INV, x0), pow_signed(B, B_INV, z0))));
+
+            let corner = (x0 + mx as isize, z0 + mz as isize);
+            origin = Some(origin.map_or(corner, |(ox, oz)| (ox.min(corner.0), oz.min(corner.1))));
+        }
+
+        let hash = match origin {
+            Some((ox, oz)) => mul(positional, mul(pow_signed(A, A_INV, -ox), pow_signed(B, B_INV, -oz))),
+            None => 0,
+        };
+
+        CycleHash {
+            hash: mix64(hash),
+            positional: mix64(positional),
+            origin,
+        }
+    }
+}
+
+fn hash_subgrid<T, const L: usize>(values: &Chunk<T, L>, a_pow: &[u64], b_pow: &[u64]) -> SubGridHash
+    where T: Default + PartialEq + Hash
+{
+    let default = T::default();
+    let mut hash = 0;
+    let mut min: Option<(usize, usize)> = None;
+
+    for (x, row) in values.rows().enumerate() {
+        for (z, v) in row.iter().enumerate() {
+            if *v == default {
+                continue;
+            }
+
+            hash = add(hash, mul(value_hash(v), mul(a_pow[x], b_pow[z])));
+            min = Some(min.map_or((x, z), |(mx, mz)| (mx.min(x), mz.min(z))));
+        }
+    }
+
+    SubGridHash { hash, min }
+}
+
+fn value_hash<T: Hash>(v: &T) -> u64 {
+    let mut hasher = FxHasher::default();
+    v.hash(&mut hasher);
+    mix64(hasher.finish()) % P
+}
+
+fn add(a: u64, b: u64) -> u64 {
+    (a + b) % P
+}
+
+const fn mul(a: u64, b: u64) -> u64 {
+    ((a as u128 * b as u128) % P as u128) as u64
+}
+
+const fn pow(mut base: u64, mut exp: u64) -> u64 {
+    let mut r = 1;
+
+    while exp > 0 {
+        if exp & 1 == 1 {
+            r = mul(r, base);
+        }
+        base = mul(base, base);
+        exp >>= 1;
+    }
+
+    r
+}
+
+// Negative powers go through `inverse`, which must be `base`'s.
+fn pow_signed(base: u64, inverse: u64, exp: isize) -> u64 {
+    match exp >= 0 {
+        true => pow(base, exp as u64),
+        false => pow(inverse, exp.unsigned_abs() as u64),
+    }
+}
+
+fn powers(base: u64, n: usize) -> Vec<u64> {
+    let mut r = Vec::with_capacity(n);
+    let mut v = 1;
+
+    for _ in 0..n {
+        r.push(v);
+        v = mul(v, base);
+    }
+
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Point;
+
+    #[test]
+    fn inverses_undo_their_base() {
+        assert_eq!(mul(A, A_INV), 1);
+        assert_eq!(mul(B, B_INV), 1);
+        assert_eq!(mul(pow_signed(A, A_INV, 5), pow_signed(A, A_INV, -5)), 1);
+    }
+
+    #[test]
+    fn observing_after_a_rewind_starts_over() {
+        let rule = crate::rule::LifeRule::conway();
+        let mut grid: Grid<u8, 8> = Grid::new();
+        for x in 0..3 {
+            grid.set(&Point::new(x, 0), 1);
+        }
+        grid.enable_history(Default::default());
+
+        let mut detector = CycleDetector::new(4);
+        detector.observe(&mut grid);
+        for _ in 0..3 {
+            grid.step(&rule);
+            detector.observe(&mut grid);
+        }
+
+        grid.rewind(2, &rule).unwrap();
+        assert!(detector.observe(&mut grid).is_none());
+        grid.step(&rule);
+        assert!(detector.observe(&mut grid).is_none());
+        grid.step(&rule);
+        assert_eq!(detector.observe(&mut grid).map(|c| c.period), Some(2));
+    }
+
+    #[test]
+    fn translation_keeps_the_hash() {
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let place = |dx: isize, dz: isize| {
+            let mut grid: Grid<u8, 8> = Grid::new();
+            for (x, z) in glider {
+                grid.set(&Point::new(x + dx, z + dz), 1);
+            }
+            grid.cycle_hash()
+        };
+
+        let here = place(0, 0);
+        for (dx, dz) in [(-37, 5), (12, -80), (-9, -9)] {
+            let there = place(dx, dz);
+            assert_eq!(there.hash, here.hash);
+            assert_ne!(there.positional, here.positional);
+        }
+    }
+}
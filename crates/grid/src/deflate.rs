@@ -0,0 +1,470 @@
+use crate::GridError;
+
+// A zlib stream holding one deflate block with the fixed Huffman codes of RFC 1951, fed by a
+// greedy LZ77 match finder with short hash chains.
+pub(crate) fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter::new();
+    out.bytes.extend_from_slice(&[0x78, 0x01]);
+
+    // Final block, fixed codes.
+    out.bits(1, 1);
+    out.bits(1, 2);
+
+    const WINDOW: usize = 1 << 15;
+    const HASH_BITS: u32 = 15;
+    const MAX_CHAIN: usize = 32;
+    const MAX_MATCH: usize = 258;
+
+    let hash = |i: usize| ((data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32).wrapping_mul(2654435761) >> (32 - HASH_BITS);
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let insert = |i: usize, head: &mut Vec<usize>, prev: &mut Vec<usize>| {
+        if i + 2 < data.len() {
+            let h = hash(i) as usize;
+            prev[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_len, mut best_dist) = (0, 0);
+
+        if i + 2 < data.len() {
+            let mut candidate = head[hash(i) as usize];
+            let max = MAX_MATCH.min(data.len() - i);
+
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || i - candidate > WINDOW - 1 {
+                    break;
+                }
+
+                let len = (0..max).take_while(|k| data[candidate + k] == data[i + k]).count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - candidate;
+                    if len == max {
+                        break;
+                    }
+                }
+
+                let next = prev[candidate % WINDOW];
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+            }
+        }
+
+        if best_len >= 3 {
+            out.length(best_len);
+            out.distance(best_dist);
+            for k in i..i + best_len {
+                insert(k, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            out.symbol(data[i] as u16);
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+
+    out.symbol(256);
+    out.flush();
+
+    out.bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    out.bytes
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for v in chunk {
+            a += *v as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+
+    b << 16 | a
+}
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193,
+    12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+pub(crate) struct BitWriter {
+    pub(crate) bytes: Vec<u8>,
+    acc: u32,
+    n: u32,
+}
+
+impl BitWriter {
+    pub(crate) fn new() -> Self {
+        Self { bytes: Vec::new(), acc: 0, n: 0 }
+    }
+
+    // Writes the low `n` bits of `v`, least significant first.
+    pub(crate) fn bits(&mut self, v: u32, n: u32) {
+        self.acc |= v << self.n;
+        self.n += n;
+        while self.n >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.n -= 8;
+        }
+    }
+
+    // Huffman codes go most significant bit first.
+    fn code(&mut self, code: u32, n: u32) {
+        self.bits(code.reverse_bits() >> (32 - n), n);
+    }
+
+    fn symbol(&mut self, s: u16) {
+        let s = s as u32;
+        match s {
+            0..=143 => self.code(0x30 + s, 8),
+            144..=255 => self.code(0x190 + s - 144, 9),
+            256..=279 => self.code(s - 256, 7),
+            _ => self.code(0xC0 + s - 280, 8),
+        }
+    }
+
+    fn length(&mut self, len: usize) {
+        let i = LENGTH_BASE.iter().rposition(|b| *b as usize <= len).unwrap();
+        self.symbol(257 + i as u16);
+        self.bits((len - LENGTH_BASE[i] as usize) as u32, LENGTH_EXTRA[i] as u32);
+    }
+
+    fn distance(&mut self, dist: usize) {
+        let i = DIST_BASE.iter().rposition(|b| *b as usize <= dist).unwrap();
+        self.code(i as u32, 5);
+        self.bits((dist - DIST_BASE[i] as usize) as u32, DIST_EXTRA[i] as u32);
+    }
+
+    pub(crate) fn flush(&mut self) {
+        if self.n > 0 {
+            self.bytes.push(self.acc as u8);
+            self.acc = 0;
+            self.n = 0;
+        }
+    }
+}
+
+/// Decompresses a zlib stream, checking its Adler-32 trailer. Fails as soon as the output would
+/// pass `limit` bytes, so that a small stream cannot inflate without bound.
+pub(crate) fn unzlib(data: &[u8], limit: usize) -> Result<Vec<u8>, GridError> {
+    if data.len() < 6 || data[0] & 0x0F != 8 || (data[0] as u16 * 256 + data[1] as u16) % 31 != 0 || data[1] & 0x20 != 0 {
+        return Err(corrupt());
+    }
+
+    let mut r = BitReader { data: &data[2..], pos: 0 };
+    let out = inflate(&mut r, limit)?;
+
+    let trailer = r.pos.div_ceil(8);
+    let expected = match data.get(2 + trailer..2 + trailer + 4) {
+        None => return Err(corrupt()),
+        Some(b) => u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+    };
+    let found = adler32(&out);
+    if expected != found {
+        return Err(GridError::ChecksumMismatch { expected: expected.into(), found: found.into() });
+    }
+
+    Ok(out)
+}
+
+fn corrupt() -> GridError {
+    GridError::ParseError("corrupt deflate stream".to_string())
+}
+
+fn too_long(limit: usize) -> GridError {
+    GridError::ParseError(format!("deflate stream inflates past the expected {} bytes", limit))
+}
+
+fn inflate(r: &mut BitReader, limit: usize) -> Result<Vec<u8>, GridError> {
+    let mut out = Vec::new();
+
+    loop {
+        let last = r.bits(1)? == 1;
+
+        match r.bits(2)? {
+            0 => {
+                r.pos = r.pos.div_ceil(8) * 8;
+                let len = r.bits(16)?;
+                let nlen = r.bits(16)?;
+                if len != !nlen & 0xFFFF {
+                    return Err(corrupt());
+                }
+
+                let start = r.pos / 8;
+                let block = r.data.get(start..start + len as usize).ok_or_else(corrupt)?;
+                if out.len() + block.len() > limit {
+                    return Err(too_long(limit));
+                }
+                out.extend_from_slice(block);
+                r.pos += len as usize * 8;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+
+                let lit = Huffman::new(&lengths)?;
+                let dist = Huffman::new(&[5; 30])?;
+                inflate_block(r, &mut out, &lit, &dist, limit)?;
+            }
+            2 => {
+                let (lit, dist) = read_dynamic_codes(r)?;
+                inflate_block(r, &mut out, &lit, &dist, limit)?;
+            }
+            _ => return Err(corrupt()),
+        }
+
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn read_dynamic_codes(r: &mut BitReader) -> Result<(Huffman, Huffman), GridError> {
+    const ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+    let nlit = r.bits(5)? as usize + 257;
+    let ndist = r.bits(5)? as usize + 1;
+    let nclen = r.bits(4)? as usize + 4;
+
+    let mut clen = [0u8; 19];
+    for i in ORDER.iter().take(nclen) {
+        clen[*i] = r.bits(3)? as u8;
+    }
+    let clen = Huffman::new(&clen)?;
+
+    let mut lengths = vec![0u8; nlit + ndist];
+    let mut i = 0;
+    while i < lengths.len() {
+        let (v, n) = match clen.decode(r)? {
+            s @ 0..=15 => (s as u8, 1),
+            16 => (*lengths[..i].last().ok_or_else(corrupt)?, 3 + r.bits(2)? as usize),
+            17 => (0, 3 + r.bits(3)? as usize),
+            _ => (0, 11 + r.bits(7)? as usize),
+        };
+
+        let run = lengths.get_mut(i..i + n).ok_or_else(corrupt)?;
+        run.fill(v);
+        i += n;
+    }
+
+    if lengths[256] == 0 {
+        return Err(corrupt());
+    }
+
+    Ok((Huffman::new(&lengths[..nlit])?, Huffman::new(&lengths[nlit..])?))
+}
+
+fn inflate_block(r: &mut BitReader, out: &mut Vec<u8>, lit: &Huffman, dist: &Huffman, limit: usize) -> Result<(), GridError> {
+    loop {
+        let s = lit.decode(r)? as usize;
+        if s != 256 && out.len() >= limit {
+            return Err(too_long(limit));
+        }
+
+        match s {
+            0..=255 => out.push(s as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = s - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err(corrupt());
+                }
+                let len = LENGTH_BASE[i] as usize + r.bits(LENGTH_EXTRA[i] as u32)? as usize;
+
+                let d = dist.decode(r)? as usize;
+                if d >= DIST_BASE.len() {
+                    return Err(corrupt());
+                }
+                let distance = DIST_BASE[d] as usize + r.bits(DIST_EXTRA[d] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(corrupt());
+                }
+                if out.len() + len > limit {
+                    return Err(too_long(limit));
+                }
+
+                // Byte by byte, since the match may overlap what it copies.
+                let start = out.len() - distance;
+                for k in 0..len {
+                    out.push(out[start + k]);
+                }
+            }
+        }
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    // In bits.
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32, GridError> {
+        let mut v = 0;
+        for i in 0..n {
+            let byte = *self.data.get(self.pos / 8).ok_or_else(corrupt)?;
+            v |= ((byte >> (self.pos % 8)) as u32 & 1) << i;
+            self.pos += 1;
+        }
+
+        Ok(v)
+    }
+}
+
+// A canonical Huffman code, decoded a bit at a time by walking the code lengths.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, GridError> {
+        let mut counts = [0u16; 16];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // More codes of some length than the shorter ones leave room for.
+        let mut left = 1i32;
+        for count in &counts[1..] {
+            left = (left << 1) - *count as i32;
+            if left < 0 {
+                return Err(corrupt());
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+
+        let mut symbols = vec![0; lengths.iter().filter(|l| **l != 0).count()];
+        for (s, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                symbols[offsets[*len as usize] as usize] = s as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, r: &mut BitReader) -> Result<u16, GridError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+
+        for len in 1..16 {
+            code |= r.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(corrupt())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(blocks: &[&[u8]]) -> Vec<u8> {
+        let mut data = vec![0x78, 0x01];
+        for (i, block) in blocks.iter().enumerate() {
+            let len = block.len() as u16;
+            data.push((i + 1 == blocks.len()) as u8);
+            data.extend_from_slice(&len.to_le_bytes());
+            data.extend_from_slice(&(!len).to_le_bytes());
+            data.extend_from_slice(block);
+        }
+
+        let all: Vec<u8> = blocks.concat();
+        data.extend_from_slice(&adler32(&all).to_be_bytes());
+        data
+    }
+
+    // Written by zlib at level 9, which picks dynamic codes for it.
+    const DYNAMIC: &str = "78daedccc111c0200800b059a9a2700ae2a1b07e97e8b3192040cdc31e50d7847576c55b411699960ecc51822811a6e0e493963194b7f5d1ccbab05ff18d1dfee4abe4051549a8b6";
+
+    fn dynamic() -> (Vec<u8>, Vec<u8>) {
+        let stream = (0..DYNAMIC.len()).step_by(2).map(|i| u8::from_str_radix(&DYNAMIC[i..i + 2], 16).unwrap()).collect();
+        let data = (0..400usize).map(|i| ((i * i * 7 + i / 3) % 23) as u8 + b'a').collect();
+        (stream, data)
+    }
+
+    #[test]
+    fn adler32_known_vector() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    }
+
+    #[test]
+    fn stored_blocks() {
+        assert_eq!(unzlib(&stored(&[b"hello, ", b"world"]), 12).unwrap(), b"hello, world");
+        assert_eq!(unzlib(&stored(&[b""]), 0).unwrap(), b"");
+    }
+
+    #[test]
+    fn dynamic_block() {
+        let (stream, data) = dynamic();
+        assert_eq!((stream[2] >> 1) & 3, 2);
+        assert_eq!(unzlib(&stream, data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_bad_adler32() {
+        let mut stream = zlib(b"some data to check");
+        *stream.last_mut().unwrap() ^= 1;
+        assert!(matches!(unzlib(&stream, 100), Err(GridError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn rejects_truncated_streams() {
+        for stream in [zlib(&[1, 2, 3, 1, 2, 3, 1, 2, 3, 4]), stored(&[b"stored bytes"]), dynamic().0] {
+            for len in 0..stream.len() {
+                assert!(unzlib(&stream[..len], 1000).is_err(), "accepted {} of {} bytes", len, stream.len());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_bad_header() {
+        let mut stream = zlib(b"abc");
+        stream[1] ^= 1;
+        assert!(unzlib(&stream, 3).is_err());
+    }
+
+    #[test]
+    fn stops_at_limit() {
+        let zeros = zlib(&vec![0; 1 << 20]);
+        assert!(zeros.len() < 8192);
+        assert!(unzlib(&zeros, 1000).is_err());
+        assert!(unzlib(&zeros, (1 << 20) - 1).is_err());
+        assert_eq!(unzlib(&zeros, 1 << 20).unwrap().len(), 1 << 20);
+
+        assert!(unzlib(&stored(&[b"hello, ", b"world"]), 11).is_err());
+        let (stream, data) = dynamic();
+        assert!(unzlib(&stream, data.len() - 1).is_err());
+    }
+}
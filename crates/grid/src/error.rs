@@ -0,0 +1,52 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridError {
+    /// A coordinate fell outside a fixed-size grid that is `size` cells per side.
+    OutOfBounds { x: usize, z: usize, size: usize },
+    /// An argument was outside the range an operation supports.
+    InvalidArgument(String),
+    /// A rule was syntactically valid but cannot be run.
+    InvalidRule(String),
+    /// Input such as a rule string or an encoded grid could not be parsed.
+    ParseError(String),
+    /// Encoded data did not match the checksum stored alongside it, or a patched grid did not
+    /// come out with the patch's target hash. CRC-32 and Adler-32 checksums are widened.
+    ChecksumMismatch { expected: u64, found: u64 },
+    /// A grid was not in the state an operation such as a patch was made against, going by the
+    /// hashes of the two states.
+    StateMismatch { expected: u64, found: u64 },
+    /// Reading or writing the underlying stream failed.
+    Io(String),
+}
+
+impl Display for GridError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GridError::OutOfBounds { x, z, size } => write!(f, "index [{},{}] is outside of grid of size {}", x, z, size),
+            GridError::InvalidArgument(s) => write!(f, "invalid argument: {}", s),
+            GridError::InvalidRule(s) => write!(f, "invalid rule: {}", s),
+            GridError::ParseError(s) => write!(f, "parse error: {}", s),
+            GridError::ChecksumMismatch { expected, found } => write!(f, "checksum mismatch: expected {:08x}, found {:08x}", expected, found),
+            GridError::StateMismatch { expected, found } => write!(f, "state mismatch: expected hash {:016x}, found {:016x}", expected, found),
+            GridError::Io(s) => write!(f, "io error: {}", s),
+        }
+    }
+}
+
+impl Error for GridError {}
+
+impl From<std::io::Error> for GridError {
+    fn from(e: std::io::Error) -> Self {
+        GridError::Io(e.to_string())
+    }
+}
+
+pub(crate) fn check_bounds(x: usize, z: usize, size: usize) -> Result<(), GridError> {
+    if x >= size || z >= size {
+        return Err(GridError::OutOfBounds { x, z, size });
+    }
+
+    Ok(())
+}
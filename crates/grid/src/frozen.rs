@@ -0,0 +1,95 @@
+use std::fmt::Display;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::Grid;
+
+/// A read-only copy of a grid at one generation, made by [`Grid::snapshot`]. It shares chunks with
+/// the grid it came from, which copies a chunk before its next write to it, so the snapshot stays
+/// as it was while the grid keeps ticking. Cloning a snapshot is a reference count, and with `T`
+/// `Send + Sync` it can be handed to another thread, such as a renderer.
+pub struct FrozenGrid<T, const L: usize> where T: Default + Clone + Display {
+    grid: Arc<Grid<T, L>>,
+}
+
+impl<T, const L: usize> FrozenGrid<T, L> where T: Default + Clone + Display {
+    /// A writable grid starting from this state, for trying out changes without touching the
+    /// original. Shares chunks as [`Grid::clone`] does.
+    pub fn to_grid(&self) -> Grid<T, L> {
+        (*self.grid).clone()
+    }
+}
+
+impl<T, const L: usize> Clone for FrozenGrid<T, L> where T: Default + Clone + Display {
+    fn clone(&self) -> Self {
+        Self { grid: self.grid.clone() }
+    }
+}
+
+impl<T, const L: usize> Deref for FrozenGrid<T, L> where T: Default + Clone + Display {
+    type Target = Grid<T, L>;
+
+    fn deref(&self) -> &Grid<T, L> {
+        &self.grid
+    }
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
+    /// Freezes the current state. Takes time in the number of allocated subgrids, not cells.
+    pub fn snapshot(&self) -> FrozenGrid<T, L> {
+        FrozenGrid { grid: Arc::new(self.clone()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::LifeRule;
+    use crate::{Point, SubGridIndex};
+
+    fn live(grid: &Grid<u8, 8>) -> Vec<(isize, isize)> {
+        let mut r: Vec<(isize, isize)> = grid.iter().filter(|(_, v)| **v != 0).map(|(p, _)| (p.x, p.z)).collect();
+        r.sort_unstable();
+        r
+    }
+
+    fn shares(a: &Grid<u8, 8>, b: &Grid<u8, 8>, x: isize, z: isize) -> bool {
+        let index = SubGridIndex::new(x, z);
+        Arc::ptr_eq(&a.values[&index].values, &b.values[&index].values)
+    }
+
+    #[test]
+    fn snapshots_survive_ticks_and_writes() {
+        let rule = LifeRule::conway();
+        let mut grid: Grid<u8, 8> = Grid::new();
+        // A glider in chunk (0, 0) and a block in the middle of chunk (5, 5).
+        for (x, z) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2), (43, 43), (44, 43), (43, 44), (44, 44)] {
+            grid.set(&Point::new(x, z), 1);
+        }
+
+        let snapshot = grid.snapshot();
+        let cells = live(&grid);
+        let hash = grid.cycle_hash();
+        assert!(shares(&snapshot, &grid, 0, 0));
+
+        for _ in 0..8 {
+            grid.step(&rule);
+        }
+        grid.set(&Point::new(-20, -20), 1);
+
+        assert_eq!(live(&snapshot), cells);
+        assert_eq!(snapshot.cycle_hash_uncached(), hash);
+        assert_eq!(snapshot.generation(), 0);
+        assert_ne!(live(&grid), cells);
+
+        // Only the chunks the glider moved through were copied.
+        assert!(!shares(&snapshot, &grid, 0, 0));
+        assert!(shares(&snapshot, &grid, 5, 5));
+
+        // Writing a grid made from the snapshot leaves the snapshot alone too.
+        let mut copy = snapshot.to_grid();
+        copy.set(&Point::new(43, 43), 0);
+        assert!(!shares(&snapshot, &copy, 5, 5));
+        assert_eq!(live(&snapshot), cells);
+    }
+}
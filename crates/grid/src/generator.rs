@@ -0,0 +1,47 @@
+use crate::chunk::Chunk;
+use crate::SubGridIndex;
+
+/// Sets each cell to `value` with probability `density`, deciding from a hash of `seed` and the
+/// cell's absolute position so the result does not depend on the order subgrids are generated in.
+/// Pass the result to `Grid::set_generator`.
+pub fn noise<T, const L: usize>(seed: u64, density: f64, value: T) -> impl Fn(&SubGridIndex) -> Chunk<T, L> + Send + Sync + 'static
+    where T: Default + Clone + Send + Sync + 'static
+{
+    let threshold = probability_threshold(density);
+
+    move |index| {
+        let x0 = index.x() * L as isize;
+        let z0 = index.z() * L as isize;
+
+        Chunk::from_fn(|x, z| {
+            match hash_point(seed, x0 + x as isize, z0 + z as isize) < threshold {
+                true => value.clone(),
+                false => T::default(),
+            }
+        })
+    }
+}
+
+/// Stateless hash of a seed and a position, well mixed enough to treat as uniform random bits.
+pub fn hash_point(seed: u64, x: isize, z: isize) -> u64 {
+    mix64(mix64(seed ^ mix64(x as u64)) ^ (z as u64))
+}
+
+// SplitMix64 finalizer.
+pub(crate) fn mix64(v: u64) -> u64 {
+    let mut v = v.wrapping_add(0x9E3779B97F4A7C15);
+    v = (v ^ (v >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    v = (v ^ (v >> 27)).wrapping_mul(0x94D049BB133111EB);
+    v ^ (v >> 31)
+}
+
+// Maps a probability onto the u64 range, so that `bits < threshold` holds with that probability.
+pub(crate) fn probability_threshold(p: f64) -> u64 {
+    if p <= 0.0 {
+        0
+    } else if p >= 1.0 {
+        u64::MAX
+    } else {
+        (p * u64::MAX as f64) as u64
+    }
+}
@@ -1,43 +1,155 @@
-use std::mem::MaybeUninit;
+use crate::chunk::Chunk;
+use crate::error::{check_bounds, GridError};
 
 pub struct GridL1<T, const L1: usize> where T: Default {
-    values: [[Option<T>; L1]; L1],
+    values: Chunk<Option<T>, L1>,
 }
 
 impl<T, const L1: usize> GridL1<T, L1> where T: Default {
     pub fn init_none() -> Self {
         Self {
-            values: allocate_2d(),
+            values: Chunk::new(),
         }
     }
+
+    pub fn get(&self, i: &L1Index<L1>) -> Option<&T> {
+        self.values[i.x][i.z].as_ref()
+    }
+
+    pub fn get_mut(&mut self, i: &L1Index<L1>) -> Option<&mut T> {
+        self.values[i.x][i.z].as_mut()
+    }
+
+    pub fn set(&mut self, i: &L1Index<L1>, v: T) {
+        self.values[i.x][i.z] = Some(v);
+    }
+
+    pub fn get_or_init<FInit: Fn() -> T>(&mut self, i: &L1Index<L1>, init: FInit) -> &mut T {
+        self.values[i.x][i.z].get_or_insert_with(init)
+    }
+
+    /// Same as `get`, for coordinates not yet checked against the grid's size.
+    pub fn try_get(&self, x: usize, z: usize) -> Result<Option<&T>, GridError> {
+        Ok(self.get(&L1Index::try_new(x, z)?))
+    }
+
+    pub fn try_set(&mut self, x: usize, z: usize, v: T) -> Result<(), GridError> {
+        self.set(&L1Index::try_new(x, z)?, v);
+        Ok(())
+    }
 }
 
 pub struct GridL2<T, const L1: usize, const L2: usize> where T: Default {
-    values: [[Option<GridL1<T, L1>>; L2]; L2],
+    values: Chunk<Option<GridL1<T, L1>>, L2>,
 }
 
 impl<T, const L1: usize, const L2: usize> GridL2<T, L1, L2> where T: Default {
     pub fn init_none() -> Self {
         Self {
-            values: allocate_2d(),
+            values: Chunk::new(),
+        }
+    }
+
+    pub fn get(&self, i: &L2Index<L1, L2>) -> Option<&T> {
+        let ((x, z), i1) = i.to_l1();
+
+        match &self.values[x][z] {
+            None => None,
+            Some(g1) => g1.get(&i1),
+        }
+    }
+
+    pub fn get_mut(&mut self, i: &L2Index<L1, L2>) -> Option<&mut T> {
+        let ((x, z), i1) = i.to_l1();
+
+        match &mut self.values[x][z] {
+            None => None,
+            Some(g1) => g1.get_mut(&i1),
         }
     }
+
+    pub fn set(&mut self, i: &L2Index<L1, L2>, v: T) {
+        let ((x, z), i1) = i.to_l1();
+
+        self.values[x][z].get_or_insert_with(GridL1::init_none).set(&i1, v);
+    }
+
+    pub fn get_or_init<FInit: Fn() -> T>(&mut self, i: &L2Index<L1, L2>, init: FInit) -> &mut T {
+        let ((x, z), i1) = i.to_l1();
+
+        self.values[x][z].get_or_insert_with(GridL1::init_none).get_or_init(&i1, init)
+    }
+
+    /// Same as `get`, for coordinates not yet checked against the grid's size.
+    pub fn try_get(&self, x: usize, z: usize) -> Result<Option<&T>, GridError> {
+        Ok(self.get(&L2Index::try_new(x, z)?))
+    }
+
+    pub fn try_set(&mut self, x: usize, z: usize, v: T) -> Result<(), GridError> {
+        self.set(&L2Index::try_new(x, z)?, v);
+        Ok(())
+    }
 }
 
 pub struct GridL3<T, const L1: usize, const L2: usize, const L3: usize> where T: Default {
-    values: [[Option<GridL2<T, L1, L2>>; L3]; L3],
+    values: Chunk<Option<GridL2<T, L1, L2>>, L3>,
 }
 
 impl<T, const L1: usize, const L2: usize, const L3: usize> GridL3<T, L1, L2, L3> where T: Default {
     pub fn init_none() -> Self {
         Self {
-            values: allocate_2d(),
+            values: Chunk::new(),
         }
     }
+
+    pub fn get(&self, i: &L3Index<L1, L2, L3>) -> Option<&T> {
+        let ((x, z), i2) = i.to_l2();
+
+        match &self.values[x][z] {
+            None => None,
+            Some(g2) => g2.get(&i2),
+        }
+    }
+
+    pub fn get_mut(&mut self, i: &L3Index<L1, L2, L3>) -> Option<&mut T> {
+        let ((x, z), i2) = i.to_l2();
+
+        match &mut self.values[x][z] {
+            None => None,
+            Some(g2) => g2.get_mut(&i2),
+        }
+    }
+
+    pub fn set(&mut self, i: &L3Index<L1, L2, L3>, v: T) {
+        let ((x, z), i2) = i.to_l2();
+
+        self.values[x][z].get_or_insert_with(GridL2::init_none).set(&i2, v);
+    }
+
+    pub fn get_or_init<FInit: Fn() -> T>(&mut self, i: &L3Index<L1, L2, L3>, init: FInit) -> &mut T {
+        let ((x, z), i2) = i.to_l2();
+
+        self.values[x][z].get_or_insert_with(GridL2::init_none).get_or_init(&i2, init)
+    }
+
+    /// Same as `get`, for coordinates not yet checked against the grid's size.
+    pub fn try_get(&self, x: usize, z: usize) -> Result<Option<&T>, GridError> {
+        Ok(self.get(&L3Index::try_new(x, z)?))
+    }
+
+    pub fn try_set(&mut self, x: usize, z: usize, v: T) -> Result<(), GridError> {
+        self.set(&L3Index::try_new(x, z)?, v);
+        Ok(())
+    }
 }
 
 //
 
+// Each level's index addresses a cell within a grid of that level, so an `L3Index` spans
+// `L1 * L2 * L3` cells per side and `to_l2` splits it into the `GridL2` slot and the index inside
+// that `GridL2`. The const parameters make the level part of the type.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Index {
     x: usize,
     z: usize,
@@ -48,88 +160,105 @@ impl Index {
         Self { x, z }
     }
 
-    pub fn to_l3<const L1: usize, const L2: usize, const L3: usize>(&self) {
+    /// Splits into the `GridL3` tile containing this index and the index within that tile.
+    pub fn to_l3<const L1: usize, const L2: usize, const L3: usize>(&self) -> ((usize, usize), L3Index<L1, L2, L3>) {
         let (qx, rx) = div_rem(self.x, L3 * L2 * L1);
         let (qz, rz) = div_rem(self.z, L3 * L2 * L1);
 
-        // let r = L3Index::<L1, L2, L3>::new(qx, qz, rx, rx);
-        // let r = ((qx, qz), L3Index::<L1, L2>::new(rx, rz));
-
-        println!("({},{})", qx, rx);
-        println!("({},{})", qz, rz);
+        ((qx, qz), L3Index { x: rx, z: rz })
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct L1Index<const L1: usize> {
     x: usize,
     z: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct L2Index<const L1: usize, const L2: usize> {
     x: usize,
     z: usize,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct L3Index<const L1: usize, const L2: usize, const L3: usize> {
     x: usize,
     z: usize,
 }
 
 impl<const L1: usize> L1Index<L1> {
+    pub const SIZE: usize = L1;
+
+    pub fn try_new(x: usize, z: usize) -> Result<Self, GridError> {
+        check_bounds(x, z, Self::SIZE)?;
+        Ok(Self { x, z })
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `x` or `z` is not below `SIZE`; see [`L1Index::try_new`].
     pub fn new(x: usize, z: usize) -> Self {
-        Self { x, z }
+        Self::try_new(x, z).unwrap_or_else(|e| panic!("{}", e))
     }
+
+    pub fn x(&self) -> usize { self.x }
+
+    pub fn z(&self) -> usize { self.z }
 }
 
 impl<const L1: usize, const L2: usize> L2Index<L1, L2> {
+    pub const SIZE: usize = L1 * L2;
+
+    pub fn try_new(x: usize, z: usize) -> Result<Self, GridError> {
+        check_bounds(x, z, Self::SIZE)?;
+        Ok(Self { x, z })
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `x` or `z` is not below `SIZE`; see [`L2Index::try_new`].
     pub fn new(x: usize, z: usize) -> Self {
-        Self { x, z }
+        Self::try_new(x, z).unwrap_or_else(|e| panic!("{}", e))
     }
 
-    pub fn to_l1(&self) /*-> ((usize, usize), L2Index<L1, L2>)*/ {
+    pub fn x(&self) -> usize { self.x }
+
+    pub fn z(&self) -> usize { self.z }
+
+    pub fn to_l1(&self) -> ((usize, usize), L1Index<L1>) {
         let (qx, rx) = div_rem(self.x, L1);
         let (qz, rz) = div_rem(self.z, L1);
 
-        let r = ((qx, qz), L1Index::<L1>::new(rx, rz));
+        ((qx, qz), L1Index { x: rx, z: rz })
     }
 }
 
 impl<const L1: usize, const L2: usize, const L3: usize> L3Index<L1, L2, L3> {
-    pub fn new(x: usize, z: usize) -> Self {
-        Self { x, z }
-    }
+    pub const SIZE: usize = L1 * L2 * L3;
 
-    pub fn to_l2(&self) {
-        let (qx, rx) = div_rem(self.x, L3);
-        let (qz, rz) = div_rem(self.z, L3);
-
-        let r = ((qx, qz), L2Index::<L1, L2>::new(rx, rz));
-
-        println!("({},{})", qx, rx);
-        println!("({},{})", qz, rz);
+    pub fn try_new(x: usize, z: usize) -> Result<Self, GridError> {
+        check_bounds(x, z, Self::SIZE)?;
+        Ok(Self { x, z })
     }
-}
 
-//
+    /// # Panics
+    ///
+    /// Panics if `x` or `z` is not below `SIZE`; see [`L3Index::try_new`].
+    pub fn new(x: usize, z: usize) -> Self {
+        Self::try_new(x, z).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-fn allocate_2d<T, const L: usize>() -> [[Option<T>; L]; L] {
-    let mut values: [[MaybeUninit<Option<T>>; L]; L] = unsafe {
-        MaybeUninit::uninit().assume_init()
-    };
+    pub fn x(&self) -> usize { self.x }
 
-    unsafe {
-        for col in &mut values[..] {
-            let mut row: [MaybeUninit<Option<T>>; L] = MaybeUninit::uninit().assume_init();
+    pub fn z(&self) -> usize { self.z }
 
-            for col in &mut row[..] {
-                *col = MaybeUninit::new(None);
-            }
+    pub fn to_l2(&self) -> ((usize, usize), L2Index<L1, L2>) {
+        let (qx, rx) = div_rem(self.x, L1 * L2);
+        let (qz, rz) = div_rem(self.z, L1 * L2);
 
-            *col = row;
-        }
+        ((qx, qz), L2Index { x: rx, z: rz })
     }
-
-    unsafe { std::mem::transmute_copy(&values) }
 }
 
 fn div_rem(n: usize, d: usize) -> (usize, usize) {
@@ -137,3 +266,28 @@ fn div_rem(n: usize, d: usize) -> (usize, usize) {
     let r = n % d;
     (q, r)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_get_and_set_check_bounds() {
+        let mut g1: GridL1<u8, 4> = GridL1::init_none();
+        g1.try_set(3, 0, 1).unwrap();
+        assert_eq!(g1.try_get(3, 0).unwrap(), Some(&1));
+        assert!(matches!(g1.try_set(4, 0, 1), Err(GridError::OutOfBounds { x: 4, z: 0, size: 4 })));
+
+        let mut g2: GridL2<u8, 4, 3> = GridL2::init_none();
+        g2.try_set(11, 5, 2).unwrap();
+        assert_eq!(g2.try_get(11, 5).unwrap(), Some(&2));
+        assert_eq!(g2.try_get(5, 11).unwrap(), None);
+        assert!(matches!(g2.try_get(0, 12), Err(GridError::OutOfBounds { size: 12, .. })));
+
+        let mut g3: GridL3<u8, 4, 3, 2> = GridL3::init_none();
+        g3.try_set(23, 0, 3).unwrap();
+        assert_eq!(g3.try_get(23, 0).unwrap(), Some(&3));
+        assert_eq!(g3.get(&L3Index::new(23, 0)), Some(&3));
+        assert!(g3.try_set(24, 24, 3).is_err());
+    }
+}
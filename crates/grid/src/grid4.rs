@@ -1,64 +1,46 @@
-use std::fmt::Display;
-use std::mem::MaybeUninit;
-
 use slab::Slab;
 
+use crate::chunk::Chunk;
+use crate::error::{check_bounds, GridError};
+
 pub struct IndexedGrid<T, const L: usize> {
     values: Vec<T>,
     values_counter: usize,
-    map: [[Option<usize>; L]; L],
+    map: Chunk<Option<usize>, L>,
 }
 
-const DEBUG: bool = false;
-
 impl<T, const L: usize> IndexedGrid<T, L> {
     pub fn init() -> Self {
         Self {
             values: Vec::with_capacity(L * L),
             values_counter: 0,
-            map: allocate_2dg(),
+            map: Chunk::new(),
         }
     }
 
-    pub fn get(&self, p: &Point) -> Option<&T> {
-        if DEBUG {
-            if p.x > L || p.z > L {
-                panic!("attempted to get at index outside of grid");
-            }
-        }
+    pub fn try_get(&self, p: &Point) -> Result<Option<&T>, GridError> {
+        check_bounds(p.x, p.z, L)?;
 
-        match self.map[p.x][p.z] {
+        Ok(match self.map[p.x][p.z] {
             None => None,
-            Some(i) => Some(unsafe { self.values.get_unchecked(i) }),
-        }
+            Some(i) => self.values.get(i),
+        })
     }
 
-    pub fn get_mut(&mut self, p: &Point) -> Option<&mut T> {
-        if DEBUG {
-            if p.x > L || p.z > L {
-                panic!("attempted to get at index outside of grid");
-            }
-        }
+    pub fn try_get_mut(&mut self, p: &Point) -> Result<Option<&mut T>, GridError> {
+        check_bounds(p.x, p.z, L)?;
 
-        match self.map[p.x][p.z] {
+        Ok(match self.map[p.x][p.z] {
             None => None,
-            Some(i) => Some(unsafe { self.values.get_unchecked_mut(i) }),
-        }
+            Some(i) => self.values.get_mut(i),
+        })
     }
 
-    pub fn get_or_init<FInit: Fn() -> T>(&mut self, p: &Point, init: FInit) -> &mut T {
-        if DEBUG {
-            if p.x > L || p.z > L {
-                panic!("attempted to get at index outside of grid");
-            }
-        }
+    pub fn try_get_or_init<FInit: Fn() -> T>(&mut self, p: &Point, init: FInit) -> Result<&mut T, GridError> {
+        check_bounds(p.x, p.z, L)?;
 
         let i = match self.map[p.x][p.z] {
             None => {
-                if DEBUG {
-                    println!("MISS: {}/{}", p.x, p.z);
-                }
-
                 let g = init();
 
                 self.values.insert(self.values_counter, g);
@@ -72,15 +54,11 @@ impl<T, const L: usize> IndexedGrid<T, L> {
             Some(i) => i,
         };
 
-        unsafe { self.values.get_unchecked_mut(i) }
+        Ok(&mut self.values[i])
     }
 
-    pub fn set(&mut self, p: &Point, v: T) {
-        if DEBUG {
-            if p.x > L || p.z > L {
-                panic!("attempted to get at index outside of grid");
-            }
-        }
+    pub fn try_set(&mut self, p: &Point, v: T) -> Result<(), GridError> {
+        check_bounds(p.x, p.z, L)?;
 
         match self.map[p.x][p.z] {
             None => {
@@ -92,66 +70,51 @@ impl<T, const L: usize> IndexedGrid<T, L> {
                 self.values[i] = v;
             }
         };
+
+        Ok(())
     }
 
-    pub fn set_or_init<FInit: Fn() -> T>(&mut self, p: &Point, init: FInit, v: T) {
-        *self.get_or_init(p, init) = v;
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L`×`L`; see [`IndexedGrid::try_get`].
+    pub fn get(&self, p: &Point) -> Option<&T> {
+        self.try_get(p).unwrap_or_else(|e| panic!("{}", e))
     }
-}
 
-//
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L`×`L`; see [`IndexedGrid::try_get_mut`].
+    pub fn get_mut(&mut self, p: &Point) -> Option<&mut T> {
+        self.try_get_mut(p).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-fn allocate_grid3<T, const L1: usize, const L2: usize, const L3: usize>() -> Grid3<T, L1, L2, L3> {
-    let mut values: [[MaybeUninit<Option<Grid2<T, L1, L2>>>; L3]; L3] = unsafe {
-        MaybeUninit::uninit().assume_init()
-    };
-    unsafe {
-        for col in &mut values[..] {
-            let mut row: [MaybeUninit<Option<Grid2<T, L1, L2>>>; L3] = MaybeUninit::uninit().assume_init();
-            for col in &mut row[..] {
-                *col = MaybeUninit::new(None);
-            }
-            *col = row;
-        }
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L`×`L`; see [`IndexedGrid::try_get_or_init`].
+    pub fn get_or_init<FInit: Fn() -> T>(&mut self, p: &Point, init: FInit) -> &mut T {
+        self.try_get_or_init(p, init).unwrap_or_else(|e| panic!("{}", e))
     }
-    unsafe { std::mem::transmute_copy(&values) }
-}
 
-fn allocate_grid2<T, const L1: usize, const L2: usize>() -> Grid2<T, L1, L2> {
-    let mut values: [[MaybeUninit<Option<Grid1<T, L1>>>; L2]; L2] = unsafe {
-        MaybeUninit::uninit().assume_init()
-    };
-    unsafe {
-        for col in &mut values[..] {
-            let mut row: [MaybeUninit<Option<Grid1<T, L1>>>; L2] = MaybeUninit::uninit().assume_init();
-            for col in &mut row[..] {
-                *col = MaybeUninit::new(None);
-            }
-            *col = row;
-        }
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L`×`L`; see [`IndexedGrid::try_set`].
+    pub fn set(&mut self, p: &Point, v: T) {
+        self.try_set(p, v).unwrap_or_else(|e| panic!("{}", e))
     }
-    unsafe { std::mem::transmute_copy(&values) }
-}
 
-fn allocate_grid1<T, const L1: usize>() -> Grid1<T, L1> {
-    let mut values: [[MaybeUninit<Option<T>>; L1]; L1] = unsafe {
-        MaybeUninit::uninit().assume_init()
-    };
-    unsafe {
-        for col in &mut values[..] {
-            let mut row: [MaybeUninit<Option<T>>; L1] = MaybeUninit::uninit().assume_init();
-            for col in &mut row[..] {
-                *col = MaybeUninit::new(None);
-            }
-            *col = row;
-        }
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L`×`L`.
+    pub fn set_or_init<FInit: Fn() -> T>(&mut self, p: &Point, init: FInit, v: T) {
+        *self.get_or_init(p, init) = v;
     }
-    unsafe { std::mem::transmute_copy(&values) }
 }
 
-pub type Grid3<T, const L1: usize, const L2: usize, const L3: usize> = [[Option<Grid2<T, L1, L2>>; L3]; L3];
-pub type Grid2<T, const L1: usize, const L2: usize> = [[Option<Grid1<T, L1>>; L2]; L2];
-pub type Grid1<T, const L1: usize> = [[Option<T>; L1]; L1];
+//
+
+pub type Grid3<T, const L1: usize, const L2: usize, const L3: usize> = Chunk<Option<Grid2<T, L1, L2>>, L3>;
+pub type Grid2<T, const L1: usize, const L2: usize> = Chunk<Option<Grid1<T, L1>>, L2>;
+pub type Grid1<T, const L1: usize> = Chunk<Option<T>, L1>;
 
 pub struct TieredGrid<T, const L1: usize, const L2: usize, const L3: usize> {
     top: IndexedGrid<IndexedGrid<IndexedGrid<T, L3>, L2>, L1>,
@@ -176,29 +139,49 @@ impl<T, const L1: usize, const L2: usize, const L3: usize> TieredGrid<T, L1, L2,
         }
     }
 
-    pub fn get(&mut self, p: &Point) -> Option<&T> {
+    pub const SIZE: usize = L1 * L2 * L3;
+
+    pub fn try_get(&self, p: &Point) -> Result<Option<&T>, GridError> {
+        check_bounds(p.x, p.z, Self::SIZE)?;
+
         let (xi3, xi2, xi1) = index_3l::<L1, L2, L3>(p.x);
         let (zi3, zi2, zi1) = index_3l::<L1, L2, L3>(p.z);
 
-        match self.top.get(&Point::new(xi3, zi3)) {
+        Ok(match self.top.get(&Point::new(xi3, zi3)) {
             None => None,
             Some(v) => match v.get(&Point::new(xi2, zi2)) {
                 None => None,
                 Some(v) => v.get(&Point::new(xi1, zi1)),
             }
-        }
+        })
     }
 
-    pub fn set(&mut self, p: &Point, v: T) {
+    pub fn try_set(&mut self, p: &Point, v: T) -> Result<(), GridError> {
+        check_bounds(p.x, p.z, Self::SIZE)?;
+
         let (xi3, xi2, xi1) = index_3l::<L1, L2, L3>(p.x);
         let (zi3, zi2, zi1) = index_3l::<L1, L2, L3>(p.z);
 
-        // println!("{}/{} {}/{} {}/{}", xi3, zi3, xi2, zi2, xi1, zi1);
-
-        let g3 = self.top.get_or_init(&Point::new(xi3, zi3), || IndexedGrid::init());
-        let g2 = g3.get_or_init(&Point::new(xi2, zi2), || IndexedGrid::init());
+        let g3 = self.top.get_or_init(&Point::new(xi3, zi3), IndexedGrid::init);
+        let g2 = g3.get_or_init(&Point::new(xi2, zi2), IndexedGrid::init);
 
         g2.set(&Point::new(xi1, zi1), v);
+
+        Ok(())
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L1 * L2 * L3` cells per side; see [`TieredGrid::try_get`].
+    pub fn get(&self, p: &Point) -> Option<&T> {
+        self.try_get(p).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L1 * L2 * L3` cells per side; see [`TieredGrid::try_set`].
+    pub fn set(&mut self, p: &Point, v: T) {
+        self.try_set(p, v).unwrap_or_else(|e| panic!("{}", e))
     }
 
     // WORKS:
@@ -243,6 +226,12 @@ impl<T, const L1: usize, const L2: usize, const L3: usize> TieredGrid<T, L1, L2,
     // }
 }
 
+impl<T, const L1: usize, const L2: usize, const L3: usize> Default for TieredGrid<T, L1, L2, L3> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 //
 
 pub struct Point {
@@ -254,151 +243,172 @@ impl Point {
     pub fn new(x: usize, z: usize) -> Self {
         Self { x, z }
     }
-}
 
-pub struct Grid4<T, const L1: usize, const L2: usize, const L3: usize> where T: Display {
-    // values: [[Option<[[Option<[[Option<T>; L3]; L3]>; L2]; L2]>; L1]; L1],
+    pub fn x(&self) -> usize {
+        self.x
+    }
 
-    grids: Vec<[[Option<T>; L2]; L2]>,
-    grids_pos: usize,
+    pub fn z(&self) -> usize {
+        self.z
+    }
+}
 
-    values: [[Option<usize>; L1]; L1],
+/// Two-level dense directory of `L2`×`L2` chunks, `L1 * L2` cells per side. Chunks are allocated on
+/// first write and freed when their last cell is removed.
+pub struct Grid4<T, const L1: usize, const L2: usize> {
+    grids: Slab<Grid4Chunk<T, L2>>,
+    values: Chunk<Option<usize>, L1>,
+    len: usize,
 }
 
-impl<T, const L1: usize, const L2: usize, const L3: usize> Grid4<T, L1, L2, L3> where T: Display {
-    pub fn init() -> Self {
-        Self {
-            grids: Vec::with_capacity(L1 * L1),
-            grids_pos: 0,
+struct Grid4Chunk<T, const L2: usize> {
+    values: Chunk<Option<T>, L2>,
+    len: usize,
+}
 
-            values: allocate_2d_index::<usize, L1, L2>(),
+impl<T, const L1: usize, const L2: usize> Grid4<T, L1, L2> {
+    pub const SIZE: usize = L1 * L2;
+
+    pub fn new() -> Self {
+        Self {
+            grids: Slab::new(),
+            values: Chunk::new(),
+            len: 0,
         }
     }
 
-    pub fn set(&mut self, p: Point, v: T) {
-        let (xi1, xi2, xi3) = index_3l::<L1, L2, L3>(p.x);
-        let (zi1, zi2, zi3) = index_3l::<L1, L2, L3>(p.z);
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-        let i1 = &self.values[xi1][zi1];
-        println!("{}", i1.is_some());
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 
-        let i = match i1 {
-            None => {
-                let g = allocate_2d::<T, L1, L2>();
+    pub fn try_get(&self, p: &Point) -> Result<Option<&T>, GridError> {
+        check_bounds(p.x, p.z, Self::SIZE)?;
 
-                self.grids.insert(self.grids_pos, g);
-                self.values[xi1][zi1] = Some(self.grids_pos);
+        let (xi1, xi2) = index_2l::<L1, L2>(p.x);
+        let (zi1, zi2) = index_2l::<L1, L2>(p.z);
 
-                let i = self.grids_pos;
-                self.grids_pos += 1;
+        Ok(match self.values[xi1][zi1] {
+            None => None,
+            Some(i) => self.grids[i].values[xi2][zi2].as_ref(),
+        })
+    }
 
-                i
+    pub fn try_get_mut(&mut self, p: &Point) -> Result<Option<&mut T>, GridError> {
+        check_bounds(p.x, p.z, Self::SIZE)?;
 
-                // self.values[xi1][zi1] = Some(&g);
+        let (xi1, xi2) = index_2l::<L1, L2>(p.x);
+        let (zi1, zi2) = index_2l::<L1, L2>(p.z);
 
-                // let x = self.grids.insert(g);
+        Ok(match self.values[xi1][zi1] {
+            None => None,
+            Some(i) => self.grids[i].values[xi2][zi2].as_mut(),
+        })
+    }
+
+    pub fn try_set(&mut self, p: &Point, v: T) -> Result<(), GridError> {
+        check_bounds(p.x, p.z, Self::SIZE)?;
 
-                // println!("{:?}", self.grids.get(x));
+        let (xi1, xi2) = index_2l::<L1, L2>(p.x);
+        let (zi1, zi2) = index_2l::<L1, L2>(p.z);
+
+        let i = match self.values[xi1][zi1] {
+            None => {
+                let i = self.grids.insert(Grid4Chunk {
+                    values: Chunk::new(),
+                    len: 0,
+                });
+                self.values[xi1][zi1] = Some(i);
+                i
             }
-            Some(i) => *i,
+            Some(i) => i,
         };
 
-        println!("[{},{}] {} -> {}", p.x, p.z, i, v);
+        let chunk = &mut self.grids[i];
+        if chunk.values[xi2][zi2].replace(v).is_none() {
+            chunk.len += 1;
+            self.len += 1;
+        }
 
-        (unsafe { self.grids.get_unchecked_mut(i) })[xi2][zi2] = Some(v);
+        Ok(())
+    }
 
+    pub fn try_remove(&mut self, p: &Point) -> Result<Option<T>, GridError> {
+        check_bounds(p.x, p.z, Self::SIZE)?;
 
-        // let r = &unsafe { self.grids.get_unchecked(v) }[xi2][zi2];
-    }
-}
+        let (xi1, xi2) = index_2l::<L1, L2>(p.x);
+        let (zi1, zi2) = index_2l::<L1, L2>(p.z);
 
-fn allocate_2dg<'a, T, const L: usize>() -> [[Option<T>; L]; L] where T: Default {
-    let mut values: [[MaybeUninit<Option<T>>; L]; L] = unsafe {
-        MaybeUninit::uninit().assume_init()
-    };
-    unsafe {
-        for col in &mut values[..] {
-            let mut row: [MaybeUninit<Option<T>>; L] = MaybeUninit::uninit().assume_init();
-            for col in &mut row[..] {
-                *col = MaybeUninit::new(None);
-            }
-            *col = row;
+        let i = match self.values[xi1][zi1] {
+            None => return Ok(None),
+            Some(i) => i,
+        };
+        let chunk = &mut self.grids[i];
+        let old = match chunk.values[xi2][zi2].take() {
+            None => return Ok(None),
+            Some(old) => old,
+        };
+
+        chunk.len -= 1;
+        self.len -= 1;
+
+        if chunk.len == 0 {
+            self.grids.remove(i);
+            self.values[xi1][zi1] = None;
         }
+
+        Ok(Some(old))
     }
-    unsafe { std::mem::transmute_copy(&values) }
-}
 
-fn allocate_2d<'a, T, const L1: usize, const L2: usize>() -> [[Option<T>; L2]; L2] {
-    let mut values: [[MaybeUninit<Option<T>>; L2]; L2] = unsafe {
-        MaybeUninit::uninit().assume_init()
-    };
-    unsafe {
-        for col in &mut values[..] {
-            let mut row: [MaybeUninit<Option<T>>; L2] = MaybeUninit::uninit().assume_init();
-            for col in &mut row[..] {
-                *col = MaybeUninit::new(None);
-            }
-            *col = row;
-        }
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L1 * L2` cells per side; see [`Grid4::try_get`].
+    pub fn get(&self, p: &Point) -> Option<&T> {
+        self.try_get(p).unwrap_or_else(|e| panic!("{}", e))
     }
-    unsafe { std::mem::transmute_copy(&values) }
-}
 
-fn allocate_2d_index<T, const L1: usize, const L2: usize>() -> [[Option<T>; L1]; L1] {
-    let mut values: [[MaybeUninit<Option<T>>; L1]; L1] = unsafe {
-        MaybeUninit::uninit().assume_init()
-    };
-    unsafe {
-        for col in &mut values[..] {
-            let mut row: [MaybeUninit<Option<T>>; L1] = MaybeUninit::uninit().assume_init();
-            for col in &mut row[..] {
-                *col = MaybeUninit::new(None);
-            }
-            *col = row;
-        }
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L1 * L2` cells per side; see [`Grid4::try_get_mut`].
+    pub fn get_mut(&mut self, p: &Point) -> Option<&mut T> {
+        self.try_get_mut(p).unwrap_or_else(|e| panic!("{}", e))
     }
-    unsafe { std::mem::transmute_copy(&values) }
-}
 
-// fn allocate_2d3<T, const L1: usize, const L2: usize, const L3: usize>() -> [[Option<[[Option<[[Option<T>; L3]; L3]>; L2]; L2]>; L1]; L1] {
-//     let mut values: [[MaybeUninit<Option<[[Option<[[Option<T>; L3]; L3]>; L2]; L2]>>; L1]; L1] = unsafe {
-//         MaybeUninit::uninit().assume_init()
-//     };
-//
-//     unsafe {
-//         for col in &mut values[..] {
-//             let mut row: [MaybeUninit<Option<[[Option<[[Option<T>; L3]; L3]>; L2]; L2]>>; L1] = MaybeUninit::uninit().assume_init();
-//
-//             for col in &mut row[..] {
-//                 *col = MaybeUninit::new(None);
-//             }
-//
-//             *col = row;
-//         }
-//     }
-//
-//     unsafe { std::mem::transmute_copy(&values) }
-// }
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L1 * L2` cells per side; see [`Grid4::try_set`].
+    pub fn set(&mut self, p: &Point, v: T) {
+        self.try_set(p, v).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-// fn allocate_2d2<'a, T, const L1: usize, const L2: usize>() -> [[Option<&'a [[Option<T>; L2]; L2]>; L1]; L1] {
-//     let mut values: [[MaybeUninit<Option<&[[Option<T>; L2]; L2]>>; L1]; L1] = unsafe {
-//         MaybeUninit::uninit().assume_init()
-//     };
-//
-//     unsafe {
-//         for col in &mut values[..] {
-//             let mut row: [MaybeUninit<Option<&[[Option<T>; L2]; L2]>>; L1] = MaybeUninit::uninit().assume_init();
-//
-//             for col in &mut row[..] {
-//                 *col = MaybeUninit::new(None);
-//             }
-//
-//             *col = row;
-//         }
-//     }
-//
-//     unsafe { std::mem::transmute_copy(&values) }
-// }
+    /// # Panics
+    ///
+    /// Panics if `p` is not within `L1 * L2` cells per side; see [`Grid4::try_remove`].
+    pub fn remove(&mut self, p: &Point) -> Option<T> {
+        self.try_remove(p).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(Point, &T)> + '_ {
+        self.values.rows().enumerate().flat_map(move |(xi1, row)| {
+            row.iter().enumerate().filter_map(move |(zi1, i)| i.map(|i| (xi1, zi1, &self.grids[i])))
+        }).flat_map(|(xi1, zi1, chunk)| {
+            chunk.values.rows().enumerate().flat_map(move |(xi2, row)| {
+                row.iter().enumerate().filter_map(move |(zi2, v)| {
+                    v.as_ref().map(|v| (Point::new(xi1 * L2 + xi2, zi1 * L2 + zi2), v))
+                })
+            })
+        })
+    }
+}
+
+impl<T, const L1: usize, const L2: usize> Default for Grid4<T, L1, L2> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 fn index_3l<const L3: usize, const L2: usize, const L1: usize>(i: usize) -> (usize, usize, usize) {
     let mut i = i;
@@ -411,14 +421,111 @@ fn index_3l<const L3: usize, const L2: usize, const L1: usize>(i: usize) -> (usi
 
     let i1 = i % L1;
 
-    if DEBUG {
-        i /= L1;
+    (i3, i2, i1)
+}
 
-        if i != 0 {
-            panic!("attempted to create index for a number not representable in [{},{},{}] leveling", L3, L2, L1);
+fn index_2l<const L1: usize, const L2: usize>(i: usize) -> (usize, usize) {
+    (i / L2, i % L2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid4_round_trips() {
+        let mut grid: Grid4<usize, 2, 3> = Grid4::new();
+        for x in 0..6 {
+            for z in 0..6 {
+                grid.try_set(&Point::new(x, z), x * 10 + z).unwrap();
+            }
         }
+        assert_eq!(grid.len(), 36);
+
+        for x in 0..6 {
+            for z in 0..6 {
+                assert_eq!(grid.try_get(&Point::new(x, z)).unwrap(), Some(&(x * 10 + z)));
+            }
+        }
+
+        *grid.try_get_mut(&Point::new(4, 1)).unwrap().unwrap() = 99;
+        assert_eq!(grid.try_remove(&Point::new(4, 1)).unwrap(), Some(99));
+        assert_eq!(grid.try_remove(&Point::new(4, 1)).unwrap(), None);
+        assert_eq!(grid.try_get(&Point::new(4, 1)).unwrap(), None);
+        assert_eq!(grid.len(), 35);
     }
 
-    (i3, i2, i1)
-}
+    #[test]
+    fn grid4_frees_and_reuses_slots() {
+        let mut grid: Grid4<u8, 2, 3> = Grid4::new();
+        grid.set(&Point::new(0, 0), 1);
+        grid.set(&Point::new(1, 2), 2);
+        grid.set(&Point::new(3, 3), 3);
+        assert_eq!(grid.grids.len(), 2);
+        let freed = grid.values[0][0].unwrap();
+
+        grid.remove(&Point::new(0, 0));
+        assert_eq!(grid.grids.len(), 2);
+        grid.remove(&Point::new(1, 2));
+        assert_eq!(grid.grids.len(), 1);
+        assert_eq!(grid.values[0][0], None);
+
+        grid.set(&Point::new(5, 0), 4);
+        assert_eq!(grid.values[1][0], Some(freed));
+        assert_eq!(grid.grids.len(), 2);
+        assert_eq!(grid.get(&Point::new(3, 3)), Some(&3));
+        assert!(!grid.is_empty());
+    }
+
+    #[test]
+    fn grid4_iter_visits_every_value() {
+        let mut grid: Grid4<usize, 3, 4> = Grid4::new();
+        let points = [(0, 0), (11, 11), (3, 7), (4, 0), (8, 2), (11, 0)];
+        for (x, z) in points {
+            grid.set(&Point::new(x, z), x * 100 + z);
+        }
+
+        let mut visited: Vec<(usize, usize, usize)> = grid.iter().map(|(p, v)| (p.x(), p.z(), *v)).collect();
+        visited.sort_unstable();
+        let mut expected: Vec<(usize, usize, usize)> = points.iter().map(|(x, z)| (*x, *z, x * 100 + z)).collect();
+        expected.sort_unstable();
+        assert_eq!(visited, expected);
+    }
+
+    #[test]
+    fn tiered_grid_round_trips() {
+        // Unequal tier sizes, so that a mixed-up tier order would collide or go out of range.
+        let mut grid: TieredGrid<usize, 2, 3, 4> = TieredGrid::new();
+        for x in 0..24 {
+            for z in 0..24 {
+                grid.try_set(&Point::new(x, z), x * 100 + z).unwrap();
+            }
+        }
+
+        for x in 0..24 {
+            for z in 0..24 {
+                assert_eq!(grid.try_get(&Point::new(x, z)).unwrap(), Some(&(x * 100 + z)));
+            }
+        }
+    }
 
+    #[test]
+    fn out_of_range_is_an_error() {
+        let mut grid: Grid4<u8, 2, 3> = Grid4::new();
+        let mut tiered: TieredGrid<u8, 2, 3, 4> = TieredGrid::new();
+
+        for (x, z) in [(6, 0), (0, 6), (100, 100)] {
+            let p = Point::new(x, z);
+            assert!(matches!(grid.try_get(&p), Err(GridError::OutOfBounds { size: 6, .. })));
+            assert!(matches!(grid.try_set(&p, 1), Err(GridError::OutOfBounds { size: 6, .. })));
+            assert!(matches!(grid.try_remove(&p), Err(GridError::OutOfBounds { size: 6, .. })));
+        }
+
+        for (x, z) in [(24, 0), (0, 24)] {
+            let p = Point::new(x, z);
+            assert!(matches!(tiered.try_get(&p), Err(GridError::OutOfBounds { size: 24, .. })));
+            assert!(matches!(tiered.try_set(&p, 1), Err(GridError::OutOfBounds { size: 24, .. })));
+        }
+        assert!(grid.is_empty());
+    }
+}
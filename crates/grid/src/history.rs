@@ -0,0 +1,397 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Display;
+use std::mem::size_of;
+use std::sync::Arc;
+
+use rustc_hash::FxHashMap;
+
+use crate::chunk::Chunk;
+use crate::replay::Edit;
+use crate::rule::Rule;
+use crate::{Grid, GridError, Point, SubGrid, SubGridIndex};
+
+#[derive(Clone, Copy, Debug)]
+pub struct HistoryConfig {
+    /// A full copy of the grid is kept every this many generations; 0 keeps only the first.
+    pub keyframe_interval: u64,
+    /// Approximate memory the history may use for chunk copies and edits. Per-tick diffs are
+    /// dropped first, oldest first, then keyframes, always keeping the newest keyframe. Chunks are
+    /// counted in full even where keyframes share them, so this errs on the high side.
+    pub max_bytes: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            keyframe_interval: 64,
+            max_bytes: 64 << 20,
+        }
+    }
+}
+
+// The state "at generation g" is the grid as the tick into g left it, before any edits made at g.
+// Each tick stores the chunks it is about to write, as they were when the previous generation was
+// reached, so that undoing it restores exactly those chunks. Edits are logged as well, so a
+// generation whose diffs were dropped can be recomputed from an earlier keyframe.
+pub(crate) struct History<T, const L: usize> {
+    config: HistoryConfig,
+    keyframes: BTreeMap<u64, FxHashMap<SubGridIndex, Arc<Chunk<T, L>>>>,
+    diffs: VecDeque<Diff<T, L>>,
+    edits: Vec<Edit<T>>,
+    // Chunks written since the current generation was reached, as they were before the first write.
+    // None for chunks that were not allocated.
+    pending: FxHashMap<SubGridIndex, Option<Arc<Chunk<T, L>>>>,
+    bytes: usize,
+    // Set when the grid was changed behind the history's back, such as through `iter_mut`.
+    stale: bool,
+}
+
+struct Diff<T, const L: usize> {
+    // The generation this diff restores.
+    generation: u64,
+    before: Vec<(SubGridIndex, Option<Arc<Chunk<T, L>>>)>,
+}
+
+impl<T, const L: usize> History<T, L> where T: Default + Clone {
+    const CHUNK_BYTES: usize = L * L * size_of::<T>() + size_of::<SubGridIndex>();
+    const EDIT_BYTES: usize = size_of::<Edit<T>>();
+
+    fn new(config: HistoryConfig) -> Self {
+        Self {
+            config,
+            keyframes: BTreeMap::new(),
+            diffs: VecDeque::new(),
+            edits: Vec::new(),
+            pending: FxHashMap::default(),
+            bytes: 0,
+            stale: false,
+        }
+    }
+
+    /// Remembers the chunk at `index` before its first write this generation.
+    pub(crate) fn record(&mut self, values: &FxHashMap<SubGridIndex, SubGrid<T, L>>, index: &SubGridIndex) {
+        if !self.pending.contains_key(index) {
+            let before = values.get(index).map(|sub| sub.values.clone());
+            self.bytes += Self::CHUNK_BYTES;
+            self.pending.insert(index.copy(), before);
+        }
+    }
+
+    pub(crate) fn record_edit(&mut self, values: &FxHashMap<SubGridIndex, SubGrid<T, L>>, generation: u64, p: &Point, v: &T) {
+        self.record(values, &p.to_subgrid_index(L as isize));
+        self.edits.push(Edit { generation, point: p.copy(), value: v.clone() });
+        self.bytes += Self::EDIT_BYTES;
+    }
+
+    pub(crate) fn mark_stale(&mut self) {
+        self.stale = true;
+    }
+
+    pub(crate) fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Closes the diff for the tick from `generation - 1` into `generation`.
+    pub(crate) fn finish_tick(&mut self, values: &FxHashMap<SubGridIndex, SubGrid<T, L>>, generation: u64) {
+        let before = self.pending.drain().collect();
+        self.diffs.push_back(Diff { generation: generation - 1, before });
+
+        if self.config.keyframe_interval > 0 && generation % self.config.keyframe_interval == 0 {
+            self.keyframe(values, generation);
+        }
+
+        self.trim();
+    }
+
+    fn keyframe(&mut self, values: &FxHashMap<SubGridIndex, SubGrid<T, L>>, generation: u64) {
+        let frame: FxHashMap<_, _> = values.iter().map(|(i, sub)| (i.copy(), sub.values.clone())).collect();
+        self.bytes += frame.len() * Self::CHUNK_BYTES;
+
+        if let Some(old) = self.keyframes.insert(generation, frame) {
+            self.bytes -= old.len() * Self::CHUNK_BYTES;
+        }
+    }
+
+    fn reset(&mut self, values: &FxHashMap<SubGridIndex, SubGrid<T, L>>, generation: u64) {
+        *self = Self::new(self.config);
+        self.keyframe(values, generation);
+    }
+
+    fn trim(&mut self) {
+        while self.bytes > self.config.max_bytes {
+            if let Some(diff) = self.diffs.pop_front() {
+                self.bytes -= diff.before.len() * Self::CHUNK_BYTES;
+            } else if self.keyframes.len() > 1 {
+                let (_, frame) = self.keyframes.pop_first().unwrap();
+                self.bytes -= frame.len() * Self::CHUNK_BYTES;
+            } else {
+                break;
+            }
+        }
+
+        // Edits from before the oldest keyframe can no longer be replayed.
+        if let Some(first) = self.keyframes.keys().next().copied() {
+            let before = self.edits.len();
+            self.edits.retain(|e| e.generation >= first);
+            self.bytes -= (before - self.edits.len()) * Self::EDIT_BYTES;
+        }
+    }
+
+    // Forgets everything after `generation`.
+    fn truncate(&mut self, generation: u64) {
+        for (_, frame) in self.keyframes.split_off(&(generation + 1)) {
+            self.bytes -= frame.len() * Self::CHUNK_BYTES;
+        }
+
+        let before = self.edits.len();
+        self.edits.retain(|e| e.generation < generation);
+        self.bytes -= (before - self.edits.len()) * Self::EDIT_BYTES;
+    }
+
+    fn earliest(&self) -> Option<u64> {
+        let diff = self.diffs.front().map(|d| d.generation);
+        let keyframe = self.keyframes.keys().next().copied();
+
+        match (diff, keyframe) {
+            (Some(d), Some(k)) => Some(d.min(k)),
+            (d, k) => d.or(k),
+        }
+    }
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display + PartialEq {
+    /// Starts keeping history from the current state, replacing any history kept so far. Writing
+    /// through `iter_mut`, `chunks_mut`, `transform` or `apply` cannot be tracked, and discards the
+    /// history up to that point.
+    pub fn enable_history(&mut self, config: HistoryConfig) {
+        let mut history = History::new(config);
+        history.keyframe(&self.values, self.generation);
+        self.history = Some(Box::new(history));
+    }
+
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+
+    /// The earliest generation `seek` can go back to, and the current one.
+    pub fn history_range(&mut self) -> Option<(u64, u64)> {
+        self.sync_history();
+
+        let earliest = self.history.as_ref()?.earliest().unwrap_or(self.generation);
+        Some((earliest, self.generation))
+    }
+
+    /// Goes back `n` generations. See `seek`.
+    pub fn rewind<R: Rule<T>>(&mut self, n: u64, rule: &R) -> Result<(), GridError> {
+        let generation = self.generation.checked_sub(n)
+            .ok_or_else(|| GridError::InvalidArgument(format!("cannot rewind {} generations from generation {}", n, self.generation)))?;
+
+        self.seek(generation, rule)
+    }
+
+    /// Restores the grid to how it was on reaching `generation`. Going back undoes recorded ticks
+    /// while their diffs are kept, and otherwise recomputes forward from the nearest earlier
+    /// keyframe under `rule`, which must be the rule the grid ran under. Going forward steps under
+    /// `rule`. Either way, whatever came after `generation` is forgotten. Seeking the current
+    /// generation undoes the edits made since it was reached.
+    pub fn seek<R: Rule<T>>(&mut self, generation: u64, rule: &R) -> Result<(), GridError> {
+        self.sync_history();
+
+        let history = match &mut self.history {
+            None => return Err(GridError::InvalidArgument("history is not enabled".to_string())),
+            Some(history) => history,
+        };
+
+        if generation > self.generation {
+            while self.generation < generation {
+                self.step(rule);
+            }
+
+            return Ok(());
+        }
+
+        let undo = history.diffs.front().is_some_and(|d| d.generation <= generation);
+
+        if undo {
+            let pending: Vec<_> = history.pending.drain().collect();
+            history.bytes -= pending.len() * History::<T, L>::CHUNK_BYTES;
+            restore(&mut self.values, pending);
+
+            while self.generation > generation {
+                let diff = history.diffs.pop_back().unwrap();
+                history.bytes -= diff.before.len() * History::<T, L>::CHUNK_BYTES;
+
+                restore(&mut self.values, diff.before);
+                self.generation = diff.generation;
+            }
+
+            history.truncate(generation);
+            self.invalidate_subgrids_to_scan();
+            self.changed = None;
+            return Ok(());
+        }
+
+        let (start, frame) = match history.keyframes.range(..=generation).next_back() {
+            None => return Err(GridError::InvalidArgument(format!("generation {} is no longer in the history", generation))),
+            Some((start, frame)) => (*start, frame),
+        };
+
+        self.values = frame.iter().map(|(i, c)| (i.copy(), SubGrid::from_shared(c.clone()))).collect();
+        self.generation = start;
+        self.to_scan = None;
+        self.changed = None;
+
+        for diff in history.diffs.drain(..) {
+            history.bytes -= diff.before.len() * History::<T, L>::CHUNK_BYTES;
+        }
+        history.bytes -= history.pending.len() * History::<T, L>::CHUNK_BYTES;
+        history.pending.clear();
+
+        // Replaying the edits records them again.
+        let edits: Vec<Edit<T>> = history.edits.iter().filter(|e| e.generation >= start && e.generation < generation).cloned().collect();
+        history.truncate(start);
+
+        let mut edits = edits.into_iter().peekable();
+        while self.generation < generation {
+            while let Some(edit) = edits.next_if(|e| e.generation == self.generation) {
+                self.set(&edit.point, edit.value);
+            }
+
+            self.step(rule);
+        }
+
+        Ok(())
+    }
+
+    fn sync_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            if history.is_stale() {
+                history.reset(&self.values, self.generation);
+            }
+        }
+    }
+
+    pub(crate) fn history_tick_start(&mut self) {
+        self.sync_history();
+    }
+}
+
+fn restore<T, const L: usize>(values: &mut FxHashMap<SubGridIndex, SubGrid<T, L>>, chunks: Vec<(SubGridIndex, Option<Arc<Chunk<T, L>>>)>)
+    where T: Default + Clone
+{
+    for (index, chunk) in chunks {
+        match chunk {
+            None => values.remove(&index),
+            Some(chunk) => values.insert(index, SubGrid::from_shared(chunk)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::LifeRule;
+
+    fn cells(grid: &Grid<u8, 8>) -> Vec<(isize, isize)> {
+        let mut r: Vec<(isize, isize)> = grid.iter().filter(|(_, v)| **v != 0).map(|(p, _)| (p.x, p.z)).collect();
+        r.sort_unstable();
+        r
+    }
+
+    fn r_pentomino() -> Grid<u8, 8> {
+        let mut grid = Grid::new();
+        for (x, z) in [(0, -1), (1, -1), (-1, 0), (0, 0), (0, 1)] {
+            grid.set(&Point::new(x, z), 1);
+        }
+        grid
+    }
+
+    // An R-pentomino run for 40 generations with history under `config`, and an edit at
+    // generation 10. Returns the grid and its cells on reaching every generation.
+    fn run(config: HistoryConfig) -> (Grid<u8, 8>, Vec<Vec<(isize, isize)>>) {
+        let rule = LifeRule::conway();
+        let mut grid = r_pentomino();
+        grid.enable_history(config);
+
+        let mut states = vec![cells(&grid)];
+        for generation in 1..=40 {
+            grid.step(&rule);
+            states.push(cells(&grid));
+
+            if generation == 10 {
+                grid.set(&Point::new(-12, 5), 1);
+                grid.set(&Point::new(-12, 6), 1);
+                grid.set(&Point::new(-12, 7), 1);
+            }
+        }
+
+        (grid, states)
+    }
+
+    #[test]
+    fn rewind_undoes_ticks() {
+        let rule = LifeRule::conway();
+        let (mut grid, states) = run(HistoryConfig::default());
+
+        for generation in [39, 30, 11, 10, 3, 0] {
+            grid.seek(generation, &rule).unwrap();
+            assert_eq!(grid.generation(), generation);
+            assert_eq!(cells(&grid), states[generation as usize], "generation {}", generation);
+        }
+    }
+
+    #[test]
+    fn rewind_recomputes_from_keyframes() {
+        let rule = LifeRule::conway();
+        // No room for diffs or old keyframes: only the newest keyframe is left to go back to.
+        let (mut grid, states) = run(HistoryConfig { keyframe_interval: 8, max_bytes: 0 });
+        assert_eq!(grid.history_range(), Some((40, 40)));
+        assert!(matches!(grid.seek(39, &rule), Err(GridError::InvalidArgument(_))));
+
+        // Room for keyframes but few diffs, so going further back replays from a keyframe, edit included.
+        let (mut grid, _) = run(HistoryConfig { keyframe_interval: 8, max_bytes: 64 * History::<u8, 8>::CHUNK_BYTES });
+        assert!(grid.history.as_ref().unwrap().diffs.front().unwrap().generation > 12);
+        for generation in [12, 11, 9] {
+            grid.seek(generation, &rule).unwrap();
+            assert_eq!(cells(&grid), states[generation as usize], "generation {}", generation);
+        }
+    }
+
+    #[test]
+    fn seek_forward_steps_without_later_edits() {
+        let rule = LifeRule::conway();
+        let (mut grid, states) = run(HistoryConfig::default());
+
+        grid.seek(20, &rule).unwrap();
+        grid.seek(40, &rule).unwrap();
+        assert_eq!(cells(&grid), states[40]);
+
+        // Going back before the edit forgets it, so running forward again leaves it out.
+        let mut unedited = r_pentomino();
+        for _ in 0..40 {
+            unedited.step(&rule);
+        }
+        grid.seek(5, &rule).unwrap();
+        grid.seek(40, &rule).unwrap();
+        assert_eq!(cells(&grid), cells(&unedited));
+        assert_ne!(cells(&grid), states[40]);
+    }
+
+    #[test]
+    fn seek_to_the_current_generation_undoes_its_edits() {
+        let rule = LifeRule::conway();
+        let (mut grid, states) = run(HistoryConfig::default());
+        grid.set(&Point::new(30, 30), 1);
+
+        grid.seek(40, &rule).unwrap();
+        assert_eq!(cells(&grid), states[40]);
+
+        // Before any tick, the keyframe taken by `enable_history` is all there is to go back to.
+        let mut grid: Grid<u8, 8> = Grid::new();
+        grid.set(&Point::new(1, 1), 1);
+        grid.enable_history(HistoryConfig::default());
+        grid.set(&Point::new(2, 2), 1);
+        grid.rewind(0, &rule).unwrap();
+        assert_eq!(cells(&grid), vec![(1, 1)]);
+    }
+}
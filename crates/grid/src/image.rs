@@ -0,0 +1,870 @@
+use std::convert::TryInto;
+use std::fmt::Display;
+use std::io::{Read, Write};
+
+use crate::deflate;
+use crate::snapshot::Crc32;
+use crate::pattern::Pattern;
+use crate::{Grid, GridError, Point};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0, 0, 0);
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+
+    /// Rec. 601 luma, as used for grayscale output.
+    pub fn luma(&self) -> u8 {
+        ((self.0 as u32 * 299 + self.1 as u32 * 587 + self.2 as u32 * 114 + 500) / 1000) as u8
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    Pgm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Pgm => "pgm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// An RGB image, row-major from the top left.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, fill: Rgb) -> Self {
+        Self { width, height, pixels: vec![fill; width * height] }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Rgb] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Rgb> {
+        match x < self.width && y < self.height {
+            true => Some(self.pixels[y * self.width + x]),
+            false => None,
+        }
+    }
+
+    /// Does nothing outside the image.
+    pub fn set(&mut self, x: usize, y: usize, c: Rgb) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = c;
+        }
+    }
+
+    /// Fills the rectangle of `w` by `h` pixels at `(x, y)`, clipped to the image.
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, c: Rgb) {
+        for y in y..(y + h).min(self.height) {
+            for x in x..(x + w).min(self.width) {
+                self.pixels[y * self.width + x] = c;
+            }
+        }
+    }
+
+    pub fn write<W: Write>(&self, w: W, format: ImageFormat) -> Result<(), GridError> {
+        match format {
+            ImageFormat::Ppm => self.write_ppm(w),
+            ImageFormat::Pgm => self.write_pgm(w),
+            ImageFormat::Png => self.write_png(w),
+        }
+    }
+
+    /// Binary PPM (P6).
+    pub fn write_ppm<W: Write>(&self, mut w: W) -> Result<(), GridError> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        w.write_all(&self.rgb_bytes())?;
+        w.flush()?;
+
+        Ok(())
+    }
+
+    /// Binary PGM (P5), converting each pixel to its luma.
+    pub fn write_pgm<W: Write>(&self, mut w: W) -> Result<(), GridError> {
+        write!(w, "P5\n{} {}\n255\n", self.width, self.height)?;
+        w.write_all(&self.pixels.iter().map(|c| c.luma()).collect::<Vec<_>>())?;
+        w.flush()?;
+
+        Ok(())
+    }
+
+    /// 8-bit RGB PNG, compressed with fixed Huffman codes. Not as small as a full encoder would
+    /// make it, but cell images are mostly runs and compress well regardless.
+    pub fn write_png<W: Write>(&self, mut w: W) -> Result<(), GridError> {
+        if self.width == 0 || self.height == 0 || self.width > u32::MAX as usize / 3 || self.height > u32::MAX as usize {
+            return Err(GridError::InvalidArgument(format!("cannot write a {}x{} image as png", self.width, self.height)));
+        }
+
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&(self.width as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // Bit depth 8, colour type 2 (RGB), default compression, filter and interlace.
+        ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        // Every scanline starts with filter type 0, none.
+        let mut raw = Vec::with_capacity(self.height * (1 + self.width * 3));
+        for row in self.pixels.chunks(self.width) {
+            raw.push(0);
+            for c in row {
+                raw.extend_from_slice(&[c.0, c.1, c.2]);
+            }
+        }
+
+        w.write_all(b"\x89PNG\r\n\x1a\n")?;
+        write_png_chunk(&mut w, b"IHDR", &ihdr)?;
+        write_png_chunk(&mut w, b"IDAT", &deflate::zlib(&raw))?;
+        write_png_chunk(&mut w, b"IEND", &[])?;
+        w.flush()?;
+
+        Ok(())
+    }
+
+    pub(crate) fn rgb_bytes(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|c| [c.0, c.1, c.2]).collect()
+    }
+}
+
+/// Draws grid regions as images, each cell a `zoom` by `zoom` square coloured by `palette`. Cells
+/// run along x to the right and along z downwards, as in `Grid::print`.
+pub struct ImageRenderer<T> {
+    palette: Box<dyn Fn(&T) -> Rgb + Send + Sync>,
+    zoom: usize,
+    grid_lines: Option<Rgb>,
+    chunk_borders: Option<Rgb>,
+}
+
+impl<T> ImageRenderer<T> where T: Default + Clone + Display {
+    pub fn new<F: Fn(&T) -> Rgb + Send + Sync + 'static>(palette: F) -> Self {
+        Self {
+            palette: Box::new(palette),
+            zoom: 1,
+            grid_lines: None,
+            chunk_borders: None,
+        }
+    }
+
+    pub fn with_zoom(mut self, zoom: usize) -> Self {
+        self.zoom = zoom;
+        self
+    }
+
+    /// Draws the top and left edge of every cell in `color`. Skipped below zoom 3, where the lines
+    /// would cover most of the cells.
+    pub fn with_grid_lines(mut self, color: Rgb) -> Self {
+        self.grid_lines = Some(color);
+        self
+    }
+
+    /// Draws the top and left edge of every subgrid in `color`, over any grid lines.
+    pub fn with_chunk_borders(mut self, color: Rgb) -> Self {
+        self.chunk_borders = Some(color);
+        self
+    }
+
+    pub fn zoom(&self) -> usize {
+        self.zoom
+    }
+
+    pub fn color(&self, v: &T) -> Rgb {
+        (self.palette)(v)
+    }
+
+    /// Renders the rectangle from `start` to `end`, inclusive. Unallocated cells are drawn as
+    /// `T::default()`.
+    pub fn render<const L: usize>(&self, grid: &Grid<T, L>, start: &Point, end: &Point) -> Result<Image, GridError> {
+        if end.x < start.x || end.z < start.z {
+            return Err(GridError::InvalidArgument(format!("image rectangle from {} to {} is empty", start, end)));
+        }
+        if self.zoom == 0 {
+            return Err(GridError::InvalidArgument("zoom must be at least 1".to_string()));
+        }
+
+        let zoom = self.zoom;
+        let (w, h) = ((end.x - start.x + 1) as usize, (end.z - start.z + 1) as usize);
+        let size = w.checked_mul(zoom)
+            .zip(h.checked_mul(zoom))
+            .filter(|(pw, ph)| pw.checked_mul(*ph).is_some_and(|n| n <= MAX_PIXELS));
+        let (pw, ph) = match size {
+            None => return Err(GridError::InvalidArgument(format!("a {}x{} region at zoom {} is too large to render", w, h, zoom))),
+            Some(size) => size,
+        };
+
+        let mut image = Image::new(pw, ph, self.color(&T::default()));
+
+        for (p, v) in grid.iter_rect(start, end) {
+            let (x, z) = ((p.x - start.x) as usize, (p.z - start.z) as usize);
+            image.fill_rect(x * zoom, z * zoom, zoom, zoom, self.color(v));
+        }
+
+        if let Some(c) = self.grid_lines.filter(|_| zoom >= 3) {
+            self.draw_lines(&mut image, start, w, h, 1, c);
+        }
+        if let Some(c) = self.chunk_borders {
+            self.draw_lines(&mut image, start, w, h, L as isize, c);
+        }
+
+        Ok(image)
+    }
+
+    // Lines along the cells whose coordinates are multiples of `every`.
+    fn draw_lines(&self, image: &mut Image, start: &Point, w: usize, h: usize, every: isize, c: Rgb) {
+        let zoom = self.zoom;
+
+        for x in 0..w {
+            if (start.x + x as isize).rem_euclid(every) == 0 {
+                image.fill_rect(x * zoom, 0, 1, image.height, c);
+            }
+        }
+        for z in 0..h {
+            if (start.z + z as isize).rem_euclid(every) == 0 {
+                image.fill_rect(0, z * zoom, image.width, 1, c);
+            }
+        }
+    }
+}
+
+// About 1 GiB of pixels, to turn a mistaken region into an error rather than an allocation failure.
+const MAX_PIXELS: usize = 1 << 28;
+
+fn write_png_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<(), GridError> {
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    w.write_all(&crc.finish().to_be_bytes())?;
+
+    Ok(())
+}
+
+//
+
+impl Image {
+    /// Reads a PNG, or a binary or plain PBM, PGM or PPM, going by its first bytes.
+    pub fn read<R: Read>(mut r: R) -> Result<Image, GridError> {
+        let mut data = Vec::new();
+        r.read_to_end(&mut data)?;
+
+        match data.first() {
+            Some(b'P') => Image::parse_pnm(&data),
+            _ => Image::parse_png(&data),
+        }
+    }
+
+    fn parse_pnm(data: &[u8]) -> Result<Image, GridError> {
+        let kind = match data.get(..2) {
+            Some([b'P', k @ b'1'..=b'6']) => *k,
+            _ => return Err(GridError::ParseError("not a pbm, pgm or ppm image".to_string())),
+        };
+
+        let mut pos = 2;
+        let width = pnm_number(data, &mut pos, usize::MAX)?;
+        let height = pnm_number(data, &mut pos, usize::MAX)?;
+        let max = match kind {
+            b'1' | b'4' => 1,
+            _ => pnm_number(data, &mut pos, usize::MAX)?,
+        };
+        if max == 0 || max > 65535 {
+            return Err(GridError::ParseError(format!("unsupported maximum sample value {}", max)));
+        }
+        check_size(width, height)?;
+
+        let channels = match kind {
+            b'3' | b'6' => 3,
+            _ => 1,
+        };
+        let count = width * height * channels;
+
+        let samples: Vec<usize> = match kind {
+            // Plain pbm rasters may run their digits together.
+            b'1' => (0..count).map(|_| pnm_number(data, &mut pos, 1)).collect::<Result<_, _>>()?,
+            b'2' | b'3' => (0..count).map(|_| pnm_number(data, &mut pos, usize::MAX)).collect::<Result<_, _>>()?,
+            b'4' => {
+                // Rows of packed bits, each padded to a whole byte.
+                let stride = width.div_ceil(8);
+                let raster = data.get(pos + 1..pos + 1 + stride * height).ok_or_else(|| truncated("pbm"))?;
+                (0..count).map(|i| {
+                    let (x, y) = (i % width, i / width);
+                    (raster[y * stride + x / 8] >> (7 - x % 8)) as usize & 1
+                }).collect()
+            }
+            _ => {
+                // A single whitespace byte separates the header from the raster.
+                let size = if max > 255 { 2 } else { 1 };
+                let raster = data.get(pos + 1..pos + 1 + count * size).ok_or_else(|| truncated("pnm"))?;
+                match size {
+                    1 => raster.iter().map(|v| *v as usize).collect(),
+                    _ => raster.chunks(2).map(|v| (v[0] as usize) << 8 | v[1] as usize).collect(),
+                }
+            }
+        };
+
+        let scale = |v: usize| ((v.min(max) * 255 + max / 2) / max) as u8;
+        let pixels = match kind {
+            // In bitmaps 1 is black.
+            b'1' | b'4' => samples.iter().map(|v| if *v == 0 { Rgb::WHITE } else { Rgb::BLACK }).collect(),
+            b'3' | b'6' => samples.chunks(3).map(|c| Rgb(scale(c[0]), scale(c[1]), scale(c[2]))).collect(),
+            _ => samples.iter().map(|v| {
+                let v = scale(*v);
+                Rgb(v, v, v)
+            }).collect(),
+        };
+
+        Ok(Image { width, height, pixels })
+    }
+
+    /// Any non-interlaced PNG. Alpha is composited over white, and 16-bit samples keep their high
+    /// byte.
+    fn parse_png(data: &[u8]) -> Result<Image, GridError> {
+        if data.get(..8) != Some(b"\x89PNG\r\n\x1a\n") {
+            return Err(GridError::ParseError("not a png, pbm, pgm or ppm image".to_string()));
+        }
+
+        let mut header = None;
+        let mut palette: Vec<Rgb> = Vec::new();
+        let mut idat = Vec::new();
+
+        let mut pos = 8;
+        loop {
+            let len = u32::from_be_bytes(png_bytes(data, pos)?) as usize;
+            let kind: [u8; 4] = png_bytes(data, pos + 4)?;
+            let body = data.get(pos + 8..pos + 8 + len).ok_or_else(|| truncated("png"))?;
+            let expected = u32::from_be_bytes(png_bytes(data, pos + 8 + len)?);
+            pos += 12 + len;
+
+            let mut crc = Crc32::new();
+            crc.update(&kind);
+            crc.update(body);
+            if crc.finish() != expected {
+                return Err(GridError::ChecksumMismatch { expected: expected.into(), found: crc.finish().into() });
+            }
+
+            match &kind {
+                b"IHDR" if body.len() == 13 => header = Some((
+                    u32::from_be_bytes(png_bytes(body, 0)?) as usize,
+                    u32::from_be_bytes(png_bytes(body, 4)?) as usize,
+                    body[8],
+                    body[9],
+                    body[12],
+                )),
+                b"PLTE" => {
+                    if body.is_empty() || body.len() % 3 != 0 || body.len() > 256 * 3 {
+                        return Err(GridError::ParseError(format!("png palette of {} bytes is not 1 to 256 colours", body.len())));
+                    }
+                    palette = body.chunks_exact(3).map(|c| Rgb(c[0], c[1], c[2])).collect();
+                }
+                b"IDAT" => idat.extend_from_slice(body),
+                b"IEND" => break,
+                _ => {}
+            }
+        }
+
+        let (width, height, depth, color, interlace) = header.ok_or_else(|| GridError::ParseError("png has no header".to_string()))?;
+        check_size(width, height)?;
+
+        let channels = match (color, depth) {
+            (0, 1 | 2 | 4 | 8 | 16) => 1,
+            (3, 1 | 2 | 4 | 8) => 1,
+            (2, 8 | 16) => 3,
+            (4, 8 | 16) => 2,
+            (6, 8 | 16) => 4,
+            _ => return Err(GridError::ParseError(format!("unsupported png colour type {} at bit depth {}", color, depth))),
+        };
+        if interlace != 0 {
+            return Err(GridError::ParseError("interlaced pngs are not supported".to_string()));
+        }
+
+        let bits = channels * depth as usize;
+        let stride = (width * bits).div_ceil(8);
+        let bpp = bits.div_ceil(8);
+
+        let raw = deflate::unzlib(&idat, height * (stride + 1))?;
+        if raw.len() < height * (stride + 1) {
+            return Err(truncated("png"));
+        }
+
+        let mut rows = vec![0u8; height * stride];
+        for y in 0..height {
+            let filter = raw[y * (stride + 1)];
+            let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+            let (done, rest) = rows.split_at_mut(y * stride);
+            let prev = if y > 0 { &done[(y - 1) * stride..] } else { &[][..] };
+            unfilter(filter, line, prev, &mut rest[..stride], bpp)?;
+        }
+
+        // Samples scaled to 8 bits; palette indices are left as they are.
+        let sample = |row: &[u8], i: usize| -> u8 {
+            match depth {
+                8 => row[i],
+                16 => row[i * 2],
+                _ => {
+                    let d = depth as usize;
+                    let v = row[i * d / 8] >> (8 - d - i * d % 8) & ((1 << d) - 1) as u8;
+                    match color {
+                        3 => v,
+                        _ => (v as u32 * 255 / ((1 << d) - 1)) as u8,
+                    }
+                }
+            }
+        };
+        let over_white = |c: u8, a: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32) + 127) / 255) as u8;
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in rows.chunks(stride) {
+            for x in 0..width {
+                let s = |c: usize| sample(row, x * channels + c);
+                pixels.push(match color {
+                    0 => Rgb(s(0), s(0), s(0)),
+                    2 => Rgb(s(0), s(1), s(2)),
+                    3 => *palette.get(s(0) as usize).ok_or_else(|| GridError::ParseError("png palette index out of range".to_string()))?,
+                    4 => {
+                        let v = over_white(s(0), s(1));
+                        Rgb(v, v, v)
+                    }
+                    _ => Rgb(over_white(s(0), s(3)), over_white(s(1), s(3)), over_white(s(2), s(3))),
+                });
+            }
+        }
+
+        Ok(Image { width, height, pixels })
+    }
+
+    /// Maps every pixel to a cell, x to the right and z downwards, for placing with `Grid::paste`.
+    pub fn to_pattern<T: Default + Clone, F: Fn(Rgb) -> T>(&self, f: F) -> Pattern<T> {
+        let mut pattern = Pattern::new(self.width, self.height);
+        for (i, c) in self.pixels.iter().enumerate() {
+            pattern.set(i % self.width, i / self.width, f(*c));
+        }
+
+        pattern
+    }
+}
+
+/// `dark` for pixels whose luma is below `level`, otherwise `light`. Suits drawings in black on
+/// white, taken as live cells on an empty grid.
+pub fn threshold<T: Clone>(level: u8, dark: T, light: T) -> impl Fn(Rgb) -> T {
+    move |c| match c.luma() < level {
+        true => dark.clone(),
+        false => light.clone(),
+    }
+}
+
+/// The state of the palette colour nearest each pixel. `palette` must not be empty.
+pub fn nearest_color<T: Clone>(palette: Vec<(Rgb, T)>) -> impl Fn(Rgb) -> T {
+    assert!(!palette.is_empty(), "palette is empty");
+
+    move |c| {
+        let d = |p: &Rgb| {
+            let (r, g, b) = (p.0 as i32 - c.0 as i32, p.1 as i32 - c.1 as i32, p.2 as i32 - c.2 as i32);
+            r * r + g * g + b * b
+        };
+
+        palette.iter().min_by_key(|(p, _)| d(p)).unwrap().1.clone()
+    }
+}
+
+fn check_size(width: usize, height: usize) -> Result<(), GridError> {
+    match width.checked_mul(height) {
+        Some(n) if n > 0 && n <= MAX_PIXELS => Ok(()),
+        _ => Err(GridError::ParseError(format!("unsupported image size {}x{}", width, height))),
+    }
+}
+
+fn truncated(format: &str) -> GridError {
+    GridError::ParseError(format!("{} image is truncated", format))
+}
+
+// The next decimal number of a pnm header or plain raster, skipping whitespace and comments, of at
+// most `digits` digits.
+fn pnm_number(data: &[u8], pos: &mut usize, digits: usize) -> Result<usize, GridError> {
+    loop {
+        match data.get(*pos) {
+            Some(b'#') => while data.get(*pos).is_some_and(|b| *b != b'\n') {
+                *pos += 1;
+            },
+            Some(b) if b.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+
+    let start = *pos;
+    while data.get(*pos).is_some_and(|b| b.is_ascii_digit()) && *pos - start < digits {
+        *pos += 1;
+    }
+
+    std::str::from_utf8(&data[start..*pos]).ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| truncated("pnm"))
+}
+
+fn png_bytes<const N: usize>(data: &[u8], pos: usize) -> Result<[u8; N], GridError> {
+    data.get(pos..pos + N)
+        .map(|b| b.try_into().unwrap())
+        .ok_or_else(|| truncated("png"))
+}
+
+fn unfilter(filter: u8, line: &[u8], prev: &[u8], out: &mut [u8], bpp: usize) -> Result<(), GridError> {
+    for i in 0..line.len() {
+        let a = if i >= bpp { out[i - bpp] } else { 0 };
+        let b = prev.get(i).copied().unwrap_or(0);
+        let c = if i >= bpp { prev.get(i - bpp).copied().unwrap_or(0) } else { 0 };
+
+        out[i] = line[i].wrapping_add(match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            4 => {
+                let p = a as i16 + b as i16 - c as i16;
+                let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+                if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+            }
+            _ => return Err(GridError::ParseError(format!("unknown png filter {}", filter))),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift, so the tests need no rand dependency.
+    fn noise(seed: u64, n: usize) -> Vec<u8> {
+        let mut s = seed;
+        (0..n).map(|_| {
+            s ^= s << 13;
+            s ^= s >> 7;
+            s ^= s << 17;
+            s as u8
+        }).collect()
+    }
+
+    fn image_from(width: usize, height: usize, f: impl Fn(usize, usize) -> Rgb) -> Image {
+        let mut image = Image::new(width, height, Rgb::BLACK);
+        for y in 0..height {
+            for x in 0..width {
+                image.set(x, y, f(x, y));
+            }
+        }
+
+        image
+    }
+
+    fn png_round_trip(image: &Image) {
+        let mut png = Vec::new();
+        image.write_png(&mut png).unwrap();
+
+        let read = Image::read(&png[..]).unwrap();
+        assert_eq!((read.width(), read.height()), (image.width(), image.height()));
+        assert!(read.pixels() == image.pixels());
+    }
+
+    fn zlib_round_trip(data: &[u8]) {
+        assert_eq!(deflate::unzlib(&deflate::zlib(data), data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn known_vectors() {
+        assert_eq!(deflate::zlib(b"hello"), [0x78, 0x01, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00, 0x06, 0x2c, 0x02, 0x15]);
+        // A literal, then matches of 258 and 41 at distance 1; checked against zlib's decoder.
+        assert_eq!(deflate::zlib(&[b'a'; 300]), [0x78, 0x01, 0x4b, 0x1c, 0x05, 0x44, 0x03, 0x00, 0xd8, 0xa8, 0x71, 0xad]);
+
+        let mut crc = Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.finish(), 0xcbf43926);
+    }
+
+    #[test]
+    fn zlib_round_trips() {
+        zlib_round_trip(&[]);
+        zlib_round_trip(&[42]);
+        zlib_round_trip(&[7; 100_000]);
+        zlib_round_trip(&noise(1, 100_000));
+        zlib_round_trip(&b"abcabcabd".repeat(5000));
+    }
+
+    #[test]
+    fn png_one_pixel() {
+        png_round_trip(&Image::new(1, 1, Rgb(12, 34, 56)));
+    }
+
+    #[test]
+    fn png_single_colour() {
+        // Nothing but matches of the longest length.
+        png_round_trip(&Image::new(300, 200, Rgb(200, 100, 50)));
+    }
+
+    #[test]
+    fn png_noise() {
+        let bytes = noise(2, 97 * 61 * 3);
+        png_round_trip(&image_from(97, 61, |x, y| {
+            let i = (y * 97 + x) * 3;
+            Rgb(bytes[i], bytes[i + 1], bytes[i + 2])
+        }));
+    }
+
+    #[test]
+    fn png_rows_around_window_size() {
+        // Repeated rows whose scanlines, width * 3 + 1 bytes, are just inside and just outside the
+        // 32 KiB window, so the only long matches are one row back.
+        for width in [10_922, 10_923, 11_000] {
+            let bytes = noise(width as u64, width * 3);
+            png_round_trip(&image_from(width, 3, |x, _| Rgb(bytes[x * 3], bytes[x * 3 + 1], bytes[x * 3 + 2])));
+        }
+    }
+
+    fn gray(v: u8) -> Rgb {
+        Rgb(v, v, v)
+    }
+
+    fn read(data: &[u8]) -> Image {
+        Image::read(data).unwrap()
+    }
+
+    #[test]
+    fn plain_pnm() {
+        // Plain bitmaps may run their digits together.
+        let p1 = read(b"P1\n# comment\n3 2\n1 0 1\n010\n");
+        assert_eq!(p1.pixels(), [Rgb::BLACK, Rgb::WHITE, Rgb::BLACK, Rgb::WHITE, Rgb::BLACK, Rgb::WHITE]);
+
+        assert_eq!(read(b"P2 2 2 255 0 128 255 64").pixels(), [gray(0), gray(128), gray(255), gray(64)]);
+        assert_eq!(read(b"P2 3 1 65535 0 32768 65535").pixels(), [gray(0), gray(128), gray(255)]);
+
+        assert_eq!(read(b"P3 2 1 255 255 0 0 0 255 10").pixels(), [Rgb(255, 0, 0), Rgb(0, 255, 10)]);
+        assert_eq!(read(b"P3 1 1 1000 1000 500 0").pixels(), [Rgb(255, 128, 0)]);
+    }
+
+    #[test]
+    fn binary_pnm() {
+        // Ten pixels a row, so each row is padded to two bytes.
+        let p4 = read(b"P4 10 2\n\xa5\xc0\x00\x40");
+        let bits = [1, 0, 1, 0, 0, 1, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let expected: Vec<Rgb> = bits.iter().map(|b| if *b == 1 { Rgb::BLACK } else { Rgb::WHITE }).collect();
+        assert_eq!(p4.pixels(), &expected[..]);
+
+        assert_eq!(read(b"P5 3 1 255\n\x00\x80\xff").pixels(), [gray(0), gray(128), gray(255)]);
+        assert_eq!(read(b"P5 2 1 65535\n\x00\x00\xff\xff").pixels(), [gray(0), gray(255)]);
+
+        assert_eq!(read(b"P6 2 1 255\n\x01\x02\x03\x04\x05\x06").pixels(), [Rgb(1, 2, 3), Rgb(4, 5, 6)]);
+        assert_eq!(read(b"P6 1 1 65535\n\xff\xff\x80\x00\x00\x00").pixels(), [Rgb(255, 128, 0)]);
+    }
+
+    #[test]
+    fn pnm_writers_round_trip() {
+        let bytes = noise(3, 13 * 7 * 3);
+        let image = image_from(13, 7, |x, y| {
+            let i = (y * 13 + x) * 3;
+            Rgb(bytes[i], bytes[i + 1], bytes[i + 2])
+        });
+
+        let mut ppm = Vec::new();
+        image.write_ppm(&mut ppm).unwrap();
+        assert!(read(&ppm).pixels() == image.pixels());
+
+        let mut pgm = Vec::new();
+        image.write_pgm(&mut pgm).unwrap();
+        let lumas: Vec<Rgb> = image.pixels().iter().map(|c| gray(c.luma())).collect();
+        assert!(read(&pgm).pixels() == &lumas[..]);
+    }
+
+    #[test]
+    fn rejects_truncated_pnm() {
+        for data in [&b"P5 3 1 255\n\x00\x80"[..], b"P3 1 1 255 1 2", b"P4 10 2\n\xa5", b"P2 2"] {
+            assert!(Image::read(data).is_err());
+        }
+    }
+
+    // Builds a png from unfiltered scanlines, one per row, applying `filters[y]` to row y.
+    fn png(width: u32, depth: u8, color: u8, palette: &[Rgb], rows: &[Vec<u8>], filters: &[u8], bpp: usize) -> Vec<u8> {
+        let mut raw = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            let prev = if y > 0 { &rows[y - 1][..] } else { &[][..] };
+            raw.push(filters[y]);
+            raw.extend(filter(filters[y], row, prev, bpp));
+        }
+
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&(rows.len() as u32).to_be_bytes());
+        ihdr.extend_from_slice(&[depth, color, 0, 0, 0]);
+
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        write_png_chunk(&mut data, b"IHDR", &ihdr).unwrap();
+        if !palette.is_empty() {
+            write_png_chunk(&mut data, b"PLTE", &palette.iter().flat_map(|c| [c.0, c.1, c.2]).collect::<Vec<_>>()).unwrap();
+        }
+        write_png_chunk(&mut data, b"IDAT", &deflate::zlib(&raw)).unwrap();
+        write_png_chunk(&mut data, b"IEND", &[]).unwrap();
+        data
+    }
+
+    fn filter(kind: u8, row: &[u8], prev: &[u8], bpp: usize) -> Vec<u8> {
+        (0..row.len()).map(|i| {
+            let a = if i >= bpp { row[i - bpp] } else { 0 };
+            let b = prev.get(i).copied().unwrap_or(0);
+            let c = if i >= bpp { prev.get(i - bpp).copied().unwrap_or(0) } else { 0 };
+            let predicted = match kind {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                _ => {
+                    let p = a as i16 + b as i16 - c as i16;
+                    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+                    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+                }
+            };
+
+            row[i].wrapping_sub(predicted)
+        }).collect()
+    }
+
+    // Packs samples of `depth` bits, most significant first.
+    fn pack(samples: &[u8], depth: usize) -> Vec<u8> {
+        let mut row = vec![0u8; (samples.len() * depth).div_ceil(8)];
+        for (i, v) in samples.iter().enumerate() {
+            row[i * depth / 8] |= v << (8 - depth - i * depth % 8);
+        }
+
+        row
+    }
+
+    #[test]
+    fn png_filters() {
+        // Each row uses a different filter, over noise so that every predictor matters.
+        let (width, height) = (7, 5);
+        let bytes = noise(4, width * height * 3);
+        let rows: Vec<Vec<u8>> = bytes.chunks(width * 3).map(|r| r.to_vec()).collect();
+
+        let image = read(&png(width as u32, 8, 2, &[], &rows, &[0, 1, 2, 3, 4], 3));
+        let expected: Vec<Rgb> = bytes.chunks(3).map(|c| Rgb(c[0], c[1], c[2])).collect();
+        assert!(image.pixels() == &expected[..]);
+
+        // Every filter on the first row too, where there is no row above.
+        for kind in 0..5 {
+            let image = read(&png(width as u32, 8, 2, &[], &rows[..1], &[kind], 3));
+            assert!(image.pixels() == &expected[..width]);
+        }
+    }
+
+    #[test]
+    fn png_palettes() {
+        let palette = [Rgb(255, 0, 0), Rgb(0, 255, 0), Rgb(0, 0, 255), Rgb(9, 9, 9), Rgb(1, 2, 3)];
+
+        for depth in [1usize, 2, 4] {
+            let colors = (1 << depth).min(palette.len());
+            let indices: Vec<Vec<u8>> = (0..3).map(|y| (0..11).map(|x| ((x * 3 + y) % colors) as u8).collect()).collect();
+            let rows: Vec<Vec<u8>> = indices.iter().map(|r| pack(r, depth)).collect();
+
+            let image = read(&png(11, depth as u8, 3, &palette[..colors], &rows, &[0, 1, 4], 1));
+            let expected: Vec<Rgb> = indices.concat().iter().map(|i| palette[*i as usize]).collect();
+            assert!(image.pixels() == &expected[..], "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_png_palettes() {
+        let rows = vec![vec![0]];
+        let valid = png(1, 8, 3, &[Rgb(1, 2, 3)], &rows, &[0], 1);
+        // The palette chunk follows the 8-byte signature and the 25-byte header chunk.
+        let (head, tail) = valid.split_at(8 + 25);
+        let tail = &tail[12 + 3..];
+
+        for len in [0, 1, 2, 4, 257 * 3] {
+            let mut data = head.to_vec();
+            write_png_chunk(&mut data, b"PLTE", &vec![7; len]).unwrap();
+            data.extend_from_slice(tail);
+
+            assert!(matches!(Image::read(&data[..]), Err(GridError::ParseError(_))), "{} bytes", len);
+        }
+
+        assert_eq!(read(&valid).pixels(), [Rgb(1, 2, 3)]);
+    }
+
+    #[test]
+    fn png_gray_depths() {
+        // Sub-byte grays scale up to the full range.
+        let rows = vec![pack(&[0, 1, 2, 3, 3], 2)];
+        assert_eq!(read(&png(5, 2, 0, &[], &rows, &[0], 1)).pixels(), [gray(0), gray(85), gray(170), gray(255), gray(255)]);
+
+        // 16-bit samples keep their high byte.
+        let rows = vec![vec![0x12, 0x34, 0xfe, 0xdc]];
+        assert_eq!(read(&png(2, 16, 0, &[], &rows, &[2], 2)).pixels(), [gray(0x12), gray(0xfe)]);
+
+        // Gray and alpha, over white.
+        let rows = vec![vec![0, 255, 0, 0]];
+        assert_eq!(read(&png(2, 8, 4, &[], &rows, &[0], 2)).pixels(), [gray(0), gray(255)]);
+    }
+
+    #[test]
+    fn png_rejects_bad_crc() {
+        let mut data = Vec::new();
+        Image::new(2, 2, Rgb::WHITE).write_png(&mut data).unwrap();
+
+        // The low byte of the width, inside IHDR.
+        data[19] ^= 1;
+        assert!(matches!(Image::read(&data[..]), Err(GridError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn png_rejects_bad_adler32() {
+        let rows = vec![vec![1, 2, 3]];
+        let mut data = png(1, 8, 2, &[], &rows, &[0], 3);
+
+        // The IDAT chunk ends with the adler-32 and then its own crc, which is rewritten to match.
+        let idat = data.windows(4).position(|w| w == b"IDAT").unwrap() - 4;
+        let len = u32::from_be_bytes(data[idat..idat + 4].try_into().unwrap()) as usize;
+        data[idat + 8 + len - 1] ^= 1;
+        let mut crc = Crc32::new();
+        crc.update(&data[idat + 4..idat + 8 + len]);
+        data[idat + 8 + len..idat + 12 + len].copy_from_slice(&crc.finish().to_be_bytes());
+
+        assert!(matches!(Image::read(&data[..]), Err(GridError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn png_rejects_truncation() {
+        let mut data = Vec::new();
+        Image::new(3, 2, Rgb(1, 2, 3)).write_png(&mut data).unwrap();
+
+        for len in 0..data.len() {
+            assert!(Image::read(&data[..len]).is_err(), "accepted {} of {} bytes", len, data.len());
+        }
+    }
+
+    #[test]
+    fn png_rejects_oversized_data() {
+        // A 1x1 image whose data inflates to a megabyte.
+        let rows = vec![vec![0; 1 << 20]];
+        let data = png(1, 8, 0, &[], &rows, &[0], 1);
+        assert!(data.len() < 8192);
+        assert!(matches!(Image::read(&data[..]), Err(GridError::ParseError(e)) if e.contains("inflates past")));
+    }
+}
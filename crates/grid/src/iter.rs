@@ -0,0 +1,159 @@
+use std::fmt::Display;
+
+use crate::chunk::Chunk;
+use crate::{Grid, Point, SubGrid, SubGridIndex};
+
+// Cells are visited chunk by chunk, in no particular chunk order; within a chunk, x-major.
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
+    /// Every allocated cell, including ones still at `T::default()`.
+    pub fn iter(&self) -> impl Iterator<Item=(Point, &T)> + '_ {
+        self.values.iter().flat_map(|(index, sub)| cells(index, sub))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=(Point, &mut T)> + '_ {
+        self.untracked_write();
+        self.values.iter_mut().flat_map(|(index, sub)| cells_mut(index, sub))
+    }
+
+    /// Allocated cells whose value satisfies `pred`.
+    pub fn iter_matching<'a, F: Fn(&T) -> bool + 'a>(&'a self, pred: F) -> impl Iterator<Item=(Point, &'a T)> + 'a {
+        self.iter().filter(move |(_, v)| pred(v))
+    }
+
+    pub fn iter_matching_mut<'a, F: Fn(&T) -> bool + 'a>(&'a mut self, pred: F) -> impl Iterator<Item=(Point, &'a mut T)> + 'a {
+        self.iter_mut().filter(move |(_, v)| pred(v))
+    }
+
+    /// Allocated cells in the rectangle from `start` to `end`, inclusive.
+    pub fn iter_rect(&self, start: &Point, end: &Point) -> impl Iterator<Item=(Point, &T)> + '_ {
+        let (start, end) = (start.copy(), end.copy());
+        let (min, max) = self.subgrid_range(&start, &end);
+
+        // Look the chunks up directly when the rectangle covers fewer of them than are allocated.
+        let area = ((max.x as i128 - min.x as i128 + 1) as u128).saturating_mul((max.z as i128 - min.z as i128 + 1) as u128);
+        let subs: Vec<(&SubGridIndex, &SubGrid<T, L>)> = match area < self.values.len() as u128 {
+            true => (min.x..=max.x)
+                .flat_map(|x| (min.z..=max.z).map(move |z| SubGridIndex::new(x, z)))
+                .filter_map(|index| self.values.get_key_value(&index))
+                .collect(),
+            false => self.values.iter()
+                .filter(|(index, _)| in_range(index, &min, &max))
+                .collect(),
+        };
+
+        subs.into_iter()
+            .flat_map(|(index, sub)| cells(index, sub))
+            .filter(move |(p, _)| p.x >= start.x && p.x <= end.x && p.z >= start.z && p.z <= end.z)
+    }
+
+    pub fn iter_rect_mut(&mut self, start: &Point, end: &Point) -> impl Iterator<Item=(Point, &mut T)> + '_ {
+        let (start, end) = (start.copy(), end.copy());
+        let (min, max) = self.subgrid_range(&start, &end);
+
+        self.untracked_write();
+        self.values.iter_mut()
+            .filter(move |(index, _)| in_range(index, &min, &max))
+            .flat_map(|(index, sub)| cells_mut(index, sub))
+            .filter(move |(p, _)| p.x >= start.x && p.x <= end.x && p.z >= start.z && p.z <= end.z)
+    }
+
+    /// Every allocated chunk with its index. Chunk `(x, z)` holds the cells from
+    /// `(x * L, z * L)` to `(x * L + L - 1, z * L + L - 1)`.
+    pub fn chunks(&self) -> impl Iterator<Item=(&SubGridIndex, &Chunk<T, L>)> + '_ {
+        self.values.iter().map(|(index, sub)| (index, &*sub.values))
+    }
+
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item=(&SubGridIndex, &mut Chunk<T, L>)> + '_ {
+        self.untracked_write();
+        self.values.iter_mut().map(|(index, sub)| (index, sub.chunk_mut()))
+    }
+
+    /// The number of allocated cells whose value satisfies `pred`.
+    pub fn population<F: Fn(&T) -> bool>(&self, pred: F) -> usize {
+        self.values.values()
+            .map(|sub| sub.values.as_slice().iter().filter(|v| pred(v)).count())
+            .sum()
+    }
+
+    /// The smallest rectangle holding every cell that differs from `T::default()`, as its minimum
+    /// and maximum corners, or None if there are no such cells.
+    pub fn bounding_box(&self) -> Option<(Point, Point)> where T: PartialEq {
+        let default = T::default();
+        let mut r: Option<(Point, Point)> = None;
+
+        for (p, v) in self.iter() {
+            if *v == default {
+                continue;
+            }
+
+            r = Some(match r {
+                None => (p.copy(), p),
+                Some((min, max)) => (
+                    Point::new(min.x.min(p.x), min.z.min(p.z)),
+                    Point::new(max.x.max(p.x), max.z.max(p.z)),
+                ),
+            });
+        }
+
+        r
+    }
+
+    fn subgrid_range(&self, start: &Point, end: &Point) -> (SubGridIndex, SubGridIndex) {
+        (start.to_subgrid_index(Grid::<T, L>::L_I), end.to_subgrid_index(Grid::<T, L>::L_I))
+    }
+}
+
+fn in_range(index: &SubGridIndex, min: &SubGridIndex, max: &SubGridIndex) -> bool {
+    index.x >= min.x && index.x <= max.x && index.z >= min.z && index.z <= max.z
+}
+
+fn cells<'a, T, const L: usize>(index: &SubGridIndex, sub: &'a SubGrid<T, L>) -> impl Iterator<Item=(Point, &'a T)> + 'a
+    where T: Default + Clone
+{
+    let (x0, z0) = (index.x * L as isize, index.z * L as isize);
+
+    sub.values.rows().enumerate().flat_map(move |(x, row)| {
+        row.iter().enumerate().map(move |(z, v)| (Point::new(x0 + x as isize, z0 + z as isize), v))
+    })
+}
+
+fn cells_mut<'a, T, const L: usize>(index: &SubGridIndex, sub: &'a mut SubGrid<T, L>) -> impl Iterator<Item=(Point, &'a mut T)> + 'a
+    where T: Default + Clone
+{
+    let (x0, z0) = (index.x * L as isize, index.z * L as isize);
+    sub.chunk_mut().rows_mut().enumerate().flat_map(move |(x, row)| {
+        row.iter_mut().enumerate().map(move |(z, v)| (Point::new(x0 + x as isize, z0 + z as isize), v))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marked(grid: &Grid<u8, 8>) -> Vec<(isize, isize)> {
+        let mut r: Vec<(isize, isize)> = grid.iter().filter(|(_, v)| **v != 0).map(|(p, _)| (p.x, p.z)).collect();
+        r.sort_unstable();
+        r
+    }
+
+    #[test]
+    fn iter_rect_mut_writes_only_the_rectangle() {
+        for (start, end) in [(Point::new(-3, 5), Point::new(9, 12)), (Point::new(-100, -100), Point::new(100, 100))] {
+            let mut grid: Grid<u8, 8> = Grid::new();
+            for x in -6..6 {
+                for z in -6..6 {
+                    grid.set(&Point::new(x * 8, z * 8), 0);
+                }
+            }
+
+            for (_, v) in grid.iter_rect_mut(&start, &end) {
+                *v = 1;
+            }
+
+            let mut expected: Vec<(isize, isize)> = grid.iter_rect(&start, &end).map(|(p, _)| (p.x, p.z)).collect();
+            expected.sort_unstable();
+            assert_eq!(marked(&grid), expected);
+            assert!(expected.iter().all(|(x, z)| *x >= start.x && *x <= end.x && *z >= start.z && *z <= end.z));
+        }
+    }
+}
@@ -1,15 +1,42 @@
+pub mod animation;
+pub mod census;
+pub mod chunk;
+pub mod cycle;
+mod deflate;
+pub mod error;
+pub mod frozen;
+pub mod generator;
 pub mod grid3;
 pub mod grid4;
+pub mod history;
+pub mod image;
+pub mod iter;
+pub mod ops;
+pub mod patch;
+pub mod pattern;
+pub mod region;
+pub mod replay;
+pub mod rule;
+pub mod run;
+pub mod snapshot;
+pub mod soup;
+pub mod stats;
+pub mod svg;
+pub mod term;
 
-use std::collections::hash_map::Entry;
 use std::fmt::{Display, Formatter};
-use std::marker::PhantomData;
-use std::mem::MaybeUninit;
-use std::ops::Index;
+use std::sync::Arc;
+use std::time::Instant;
 
-use lru::LruCache;
 use rustc_hash::{FxHashMap, FxHashSet};
 
+use crate::chunk::Chunk;
+use crate::cycle::SubGridHash;
+use crate::history::History;
+pub use crate::error::GridError;
+use crate::stats::TickReport;
+
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub struct Point {
     x: isize,
     z: isize,
@@ -20,6 +47,14 @@ impl Point {
         Self { x, z }
     }
 
+    pub fn x(&self) -> isize {
+        self.x
+    }
+
+    pub fn z(&self) -> isize {
+        self.z
+    }
+
     pub fn copy(&self) -> Self { Self { x: self.x, z: self.z } }
 
     fn to_subgrid_index(&self, l_i: isize) -> SubGridIndex {
@@ -121,9 +156,29 @@ pub struct Grid<T, const L: usize> where T: Default + Clone + Display {
 
     to_scan: Option<Vec<SubGridIndex>>,
     // sub_cache: LruCache<SubGridIndex, &'a SubGrid<T, L>>,
+
+    generation: u64,
+    rule_id: Option<String>,
+
+    generator: Option<Generator<T, L>>,
+    // Subgrids written since the last tick, whose rings the next tick generates. Only kept while
+    // a generator is installed; None means unknown, and the ring of every subgrid is generated.
+    changed: Option<FxHashSet<SubGridIndex>>,
+    free_empty_subgrids: bool,
+
+    history: Option<Box<History<T, L>>>,
+    tick_hook: Option<TickHook<T, L>>,
 }
 
-impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
+/// Produces the initial contents of a subgrid the first time it is allocated. Must be
+/// deterministic in the index for worlds to be reproducible.
+pub type Generator<T, const L: usize> = Arc<dyn Fn(&SubGridIndex) -> Chunk<T, L> + Send + Sync>;
+
+/// Called at the end of every tick with the grid and the tick's report, such as to record frames
+/// (see `animation::FrameRecorder`) without changing the loop that drives the grid.
+pub type TickHook<T, const L: usize> = Box<dyn FnMut(&Grid<T, L>, &TickReport) + Send + Sync>;
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
     pub const L_I: isize = L as isize;
 
     pub fn new() -> Grid<T, L> {
@@ -131,9 +186,58 @@ impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
             values: FxHashMap::default(),
             to_scan: None,
             // sub_cache: LruCache::new(3),
+            generation: 0,
+            rule_id: None,
+            generator: None,
+            changed: None,
+            free_empty_subgrids: false,
+            history: None,
+            tick_hook: None,
         }
     }
 
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn rule_id(&self) -> Option<&str> {
+        self.rule_id.as_deref()
+    }
+
+    pub fn set_rule_id(&mut self, rule_id: Option<String>) {
+        self.rule_id = rule_id;
+    }
+
+    /// Fills subgrids from `generator` instead of `T::default()` when `set` or `tick` first
+    /// touches them. Each tick generates the Moore ring around the subgrids written since the
+    /// previous one, so the world grows only as far as activity reaches it. The first tick after
+    /// this call, or after a bulk write such as `transform` or `iter_mut`, generates the ring around
+    /// every allocated subgrid instead. `get` never generates; it returns `None` for untouched
+    /// subgrids.
+    pub fn set_generator<F: Fn(&SubGridIndex) -> Chunk<T, L> + Send + Sync + 'static>(&mut self, generator: F) {
+        self.generator = Some(Arc::new(generator));
+        self.changed = None;
+    }
+
+    pub fn clear_generator(&mut self) {
+        self.generator = None;
+        self.changed = None;
+    }
+
+    pub fn set_tick_hook<F: FnMut(&Grid<T, L>, &TickReport) + Send + Sync + 'static>(&mut self, hook: F) {
+        self.tick_hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_tick_hook(&mut self) {
+        self.tick_hook = None;
+    }
+
+    /// Drops subgrids whose cells are all `T::default()` at the end of each tick. Off by default,
+    /// since `get` then returns `None` rather than `Some(&T::default())` for their cells.
+    pub fn set_free_empty_subgrids(&mut self, free: bool) {
+        self.free_empty_subgrids = free;
+    }
+
     //
 
     pub fn get(&self, p: &Point) -> Option<&T> {
@@ -146,91 +250,116 @@ impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
     }
 
     pub fn set(&mut self, p: &Point, v: T) {
-        let sub = self.get_subgrid_or_expand(p);
+        if let Some(history) = &mut self.history {
+            history.record_edit(&self.values, self.generation, p, &v);
+        }
 
-        sub.set(&p.to_subgrid_point(Grid::<T, L>::L_I), v);
+        self.write(p, v);
     }
 
-    pub fn tick<FVisit: Fn(&Point) -> &Vec<Offset>, FUpdate: Fn(&Point, Option<&T>, Vec<Option<&T>>) -> Vec<Update<T>>>(
+    // Grid is unbounded, so these never fail; they exist so that code written against the
+    // fixed-size backends in grid4 can also take a Grid.
+
+    pub fn try_get(&self, p: &Point) -> Result<Option<&T>, GridError> {
+        Ok(self.get(p))
+    }
+
+    pub fn try_set(&mut self, p: &Point, v: T) -> Result<(), GridError> {
+        self.set(p, v);
+        Ok(())
+    }
+
+    pub fn tick<'v, FVisit: Fn(&Point) -> &'v Vec<Offset>, FUpdate: Fn(&Point, Option<&T>, Vec<Option<&T>>) -> Vec<Update<T>>>(
         &mut self,
         visitor: FVisit,
         updater: FUpdate,
-    ) {
+    ) -> TickReport where T: PartialEq {
+        let start = Instant::now();
+        self.history_tick_start();
+        let subgrids_before = self.values.len();
+
         let mut updates = Vec::new();
 
-        // let mut hit = 0;
-        // let mut miss = 0;
+        if let Some(generator) = self.generator.clone() {
+            let sources: Vec<SubGridIndex> = match self.changed.replace(FxHashSet::default()) {
+                Some(changed) => changed.into_iter().collect(),
+                None => self.values.keys().map(|i| i.copy()).collect(),
+            };
+
+            for source in &sources {
+                for sub_index in source.moore_neighbors(1, true) {
+                    if !self.values.contains_key(&sub_index) {
+                        if let Some(history) = &mut self.history {
+                            history.record(&self.values, &sub_index);
+                        }
+                        let chunk = generator(&sub_index);
+                        self.values.insert(sub_index, SubGrid::from_chunk(chunk));
+                        self.to_scan = None;
+                    }
+                }
+            }
+        }
 
         for sub_index in self.subgrids_to_scan() {
-            let sub = self.values.get(&sub_index);
-            // println!("{}", sub.is_some());
-
-            let start = Point::new(sub_index.x * Grid::<T, L>::L_I, sub_index.z * Grid::<T, L>::L_I);
-            let end = Point::new(sub_index.x * Grid::<T, L>::L_I + Grid::<T, L>::L_I, sub_index.z * Grid::<T, L>::L_I + Grid::<T, L>::L_I);
-
-            for x in 0..Grid::<T, L>::L_I {
-                for z in 0..Grid::<T, L>::L_I {
-                    let point = Point::new(start.x + x, start.z + z);
-
-                    //
-
-                    let neighbor_offsets = visitor(&point);
-                    let mut neighbor_values = Vec::new();
-                    for neighbor_offset in neighbor_offsets {
-                        let neighbor_point = point.shift(neighbor_offset);
-
-                        // println!("{} in {},{} <{}> ? {}", neighbor_point, start, end, sub.is_some(), neighbor_point.is_in_range(&start, &end));
-
-                        let value = match neighbor_point.is_in_range(&start, &end) {
-                            false => {
-                                // miss += 1;
-                                self.get(&neighbor_point)
-                            }
-                            true => {
-                                match sub {
-                                    None => {
-                                        // miss += 1;
-                                        self.get(&neighbor_point)
-                                    }
-                                    Some(v) => {
-                                        // hit += 1;
-                                        Some(self.get_in_known_subgrid(v, &neighbor_point))
-                                    }
-                                }
-                            }
-                        };
-
-                        neighbor_values.push(value);
-                    }
+            let values = &self.values;
+            Grid::<T, L>::scan_subgrid(&sub_index, |i| values.get(i), &visitor, &updater, &mut updates);
+        }
 
-                    //
+        let default = T::default();
 
-                    let value = match sub {
-                        None => None,
-                        Some(v) => {
-                            Some(v.get(&point.to_subgrid_point(Grid::<T, L>::L_I)))
-                        }
-                    };
+        // Each touched cell's liveness before the tick and after its latest update, so that births
+        // and deaths count cells that changed, however many updates they took.
+        let mut touched: FxHashMap<Point, (bool, bool)> = FxHashMap::default();
 
-                    updates.append(&mut updater(&point, value, neighbor_values));
-                }
+        for update in &updates {
+            let old = self.get(&update.p);
+            let was_live = old.is_some_and(|v| *v != default);
+            let new = (update.f)(old).unwrap_or_default();
+            let is_live = new != default;
+
+            touched.entry(update.p.copy()).or_insert((was_live, was_live)).1 = is_live;
+
+            if let Some(history) = &mut self.history {
+                history.record(&self.values, &update.p.to_subgrid_index(Grid::<T, L>::L_I));
             }
+            self.write_counted(&update.p, new, was_live, is_live);
         }
 
-        for update in &updates {
-            let old = self.get(&update.p);
-            let new = (update.f)(old);
+        let births = touched.values().filter(|(before, after)| !*before && *after).count();
+        let deaths = touched.values().filter(|(before, after)| *before && !*after).count();
 
-            // if new.is_none() {
-            //     println!("UNSET: {}", update.p);
-            // } else {
-            //     println!("SET V: {} => {}", update.p, new.as_ref().unwrap());
-            // }
+        let subgrids_allocated = self.values.len() - subgrids_before;
+        let subgrids_freed = match self.free_empty_subgrids {
+            true => self.free_empty(),
+            false => 0,
+        };
 
-            self.set(&update.p, new.unwrap_or_default());
+        self.generation += 1;
+
+        if let Some(history) = &mut self.history {
+            history.finish_tick(&self.values, self.generation);
+        }
+
+        let (population, active_subgrids) = self.count_population();
+
+        let report = TickReport {
+            generation: self.generation,
+            births,
+            deaths,
+            population,
+            subgrids: self.values.len(),
+            active_subgrids,
+            subgrids_allocated,
+            subgrids_freed,
+            elapsed: start.elapsed(),
+        };
+
+        if let Some(mut hook) = self.tick_hook.take() {
+            hook(self, &report);
+            self.tick_hook = Some(hook);
         }
 
-        // println!("Hit: {} | Miss: {}", hit, miss);
+        report
     }
 
     /*
@@ -243,7 +372,6 @@ impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
     */
 
     pub fn print<FShouldDisplay: Fn(&T) -> bool>(&mut self, should_display: FShouldDisplay) -> String {
-        let (min_sub, max_sub) = self.find_subgrid_index_bounds();
         let (min, max) = self.find_grid_point_bounds();
 
         // println!("Min sub: {},{}", min_sub.x, min_sub.z);
@@ -259,31 +387,16 @@ impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
             rows.push(" ".repeat(len_x));
         }
 
-        for (sub_index, sub) in &self.values {
-            let sub_abs_x = sub_index.x * Grid::<T, L>::L_I;
-            let sub_abs_z = sub_index.z * Grid::<T, L>::L_I;
-            let sub_rel_x = (sub_abs_x - min.x) as usize;
-            let sub_rel_z = (sub_abs_z - min.z) as usize;
-
-            // println!("Sub index: {} {} | Sub abs: {} {} | Rel: {} {}", sub_index.x, sub_index.z, sub_abs_x, sub_abs_z, sub_rel_x, sub_rel_z);
-
-            for x in 0..L {
-                let this_rel_x = sub_rel_x + x;
-
-                for z in 0..L {
-                    let point = SubGridPoint::new(x, z);
-                    let value = sub.get(&point);
+        for (point, value) in self.iter() {
+            if should_display(value) {
+                let rel_x = (point.x - min.x) as usize;
+                let rel_z = (point.z - min.z) as usize;
 
-                    if should_display(value) {
-                        let this_rel_z = sub_rel_z + z;
-
-                        rows[this_rel_z].replace_range(this_rel_x..=this_rel_x, "#");
-                    }
-                }
+                rows[rel_z].replace_range(rel_x..=rel_x, "#");
             }
         }
 
-        let mut p = String::new();
+        let p = String::new();
 
         // p.push_str(&format!("+{}+\n", "-".repeat(len_x)));
         // for row in &rows {
@@ -304,6 +417,15 @@ impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
 
     //
 
+    // For writes that bypass `write`, so neither the history nor the changed set sees them; both
+    // start over from whatever state the grid is in next.
+    pub(crate) fn untracked_write(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.mark_stale();
+        }
+        self.changed = None;
+    }
+
     fn invalidate_subgrids_to_scan(&mut self) {
         self.to_scan = None;
     }
@@ -314,7 +436,7 @@ impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
                 let mut checked_neighbors = FxHashSet::default();
                 let mut to_scan = Vec::new();
 
-                for (sub_index, sub) in &self.values {
+                for sub_index in self.values.keys() {
                     to_scan.push(sub_index.copy());
 
                     for neighbor in sub_index.moore_neighbors(1, false) {
@@ -340,12 +462,41 @@ impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
         self.values.get(&index)
     }
 
+    fn write(&mut self, p: &Point, v: T) {
+        self.mark_changed(p);
+        let sub = self.get_subgrid_or_expand(p);
+
+        sub.set(&p.to_subgrid_point(Grid::<T, L>::L_I), v);
+    }
+
+    // A write by `tick`, which keeps the subgrid's live count up to date instead of dropping it.
+    fn write_counted(&mut self, p: &Point, v: T, was_live: bool, is_live: bool) {
+        self.mark_changed(p);
+        let sub = self.get_subgrid_or_expand(p);
+        let live = sub.live;
+
+        sub.set(&p.to_subgrid_point(Grid::<T, L>::L_I), v);
+        sub.live = live.map(|n| n + is_live as usize - was_live as usize);
+    }
+
+    fn mark_changed(&mut self, p: &Point) {
+        if let Some(changed) = &mut self.changed {
+            changed.insert(p.to_subgrid_index(Grid::<T, L>::L_I));
+        }
+    }
+
     fn get_subgrid_or_expand(&mut self, p: &Point) -> &mut SubGrid<T, L> {
         let mut changed = false;
 
-        let r = self.values.entry(p.to_subgrid_index(Grid::<T, L>::L_I)).or_insert_with(|| {
+        let index = p.to_subgrid_index(Grid::<T, L>::L_I);
+        let generator = &self.generator;
+
+        let r = self.values.entry(index.copy()).or_insert_with(|| {
             changed = true;
-            SubGrid::new()
+            match generator {
+                None => SubGrid::new(),
+                Some(generator) => SubGrid::from_chunk(generator(&index)),
+            }
         });
 
         if changed {
@@ -355,8 +506,46 @@ impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
         r
     }
 
-    fn get_in_known_subgrid<'b, 'c>(&self, sub: &'b SubGrid<T, L>, p: &'c Point) -> &'b T {
-        sub.get(&p.to_subgrid_point(Grid::<T, L>::L_I))
+    // Collects the updates for every cell of one subgrid. Subgrids are resolved through `lookup` so
+    // that backends other than `values` (see `region::PagedGrid`) can share the scan.
+    fn scan_subgrid<'s, 'v, FLookup, FVisit, FUpdate>(
+        sub_index: &SubGridIndex,
+        lookup: FLookup,
+        visitor: &FVisit,
+        updater: &FUpdate,
+        updates: &mut Vec<Update<T>>,
+    ) where FLookup: Fn(&SubGridIndex) -> Option<&'s SubGrid<T, L>>,
+            FVisit: Fn(&Point) -> &'v Vec<Offset>,
+            FUpdate: Fn(&Point, Option<&T>, Vec<Option<&T>>) -> Vec<Update<T>>,
+            T: 's,
+    {
+        let sub = lookup(sub_index);
+
+        let start = Point::new(sub_index.x * Grid::<T, L>::L_I, sub_index.z * Grid::<T, L>::L_I);
+        let end = Point::new(start.x + Grid::<T, L>::L_I - 1, start.z + Grid::<T, L>::L_I - 1);
+
+        for x in 0..Grid::<T, L>::L_I {
+            for z in 0..Grid::<T, L>::L_I {
+                let point = Point::new(start.x + x, start.z + z);
+
+                let neighbor_offsets = visitor(&point);
+                let mut neighbor_values = Vec::with_capacity(neighbor_offsets.len());
+                for neighbor_offset in neighbor_offsets {
+                    let neighbor_point = point.shift(neighbor_offset);
+
+                    let neighbor_sub = match neighbor_point.is_in_range(&start, &end) {
+                        true => sub,
+                        false => lookup(&neighbor_point.to_subgrid_index(Grid::<T, L>::L_I)),
+                    };
+
+                    neighbor_values.push(neighbor_sub.map(|v| v.get(&neighbor_point.to_subgrid_point(Grid::<T, L>::L_I))));
+                }
+
+                let value = sub.map(|v| v.get(&SubGridPoint::new(x as usize, z as usize)));
+
+                updates.append(&mut updater(&point, value, neighbor_values));
+            }
+        }
     }
 
     //
@@ -367,7 +556,7 @@ impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
         let mut max_sub_x = isize::MIN;
         let mut max_sub_z = isize::MIN;
 
-        for (p, _) in &self.values {
+        for p in self.values.keys() {
             if p.x < min_sub_x { min_sub_x = p.x }
             if p.z < min_sub_z { min_sub_z = p.z }
             if p.x > max_sub_x { max_sub_x = p.x }
@@ -389,10 +578,34 @@ impl<'a, T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
     }
 }
 
+impl<T, const L: usize> Default for Grid<T, L> where T: Default + Clone + Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clones share every chunk with the original until one of them writes to it, so cloning costs
+/// one reference count per allocated subgrid. History and the tick hook are not carried over.
+impl<T, const L: usize> Clone for Grid<T, L> where T: Default + Clone + Display {
+    fn clone(&self) -> Self {
+        Grid {
+            values: self.values.clone(),
+            to_scan: self.to_scan.clone(),
+            generation: self.generation,
+            rule_id: self.rule_id.clone(),
+            generator: self.generator.clone(),
+            changed: self.changed.clone(),
+            free_empty_subgrids: self.free_empty_subgrids,
+            history: None,
+            tick_hook: None,
+        }
+    }
+}
+
 //
 
-#[derive(PartialEq, Eq, Hash, Clone)]
-struct SubGridIndex {
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct SubGridIndex {
     x: isize,
     z: isize,
 }
@@ -402,6 +615,14 @@ impl SubGridIndex {
         Self { x, z }
     }
 
+    pub fn x(&self) -> isize {
+        self.x
+    }
+
+    pub fn z(&self) -> isize {
+        self.z
+    }
+
     pub fn copy(&self) -> Self {
         Self { x: self.x, z: self.z }
     }
@@ -453,17 +674,28 @@ impl SubGridPoint {
     }
 }
 
+// Chunks are shared between clones of a grid, and with its history, until one of them writes.
+#[derive(Clone)]
 struct SubGrid<T, const L: usize> where T: Default + Clone {
-    values: [[T; L]; L],
+    values: Arc<Chunk<T, L>>,
+    // Cached by `cycle`, cleared on every write.
+    hash: Option<SubGridHash>,
+    // Cells other than `T::default()`. Cached by `stats`, cleared on every write but those of
+    // `tick`, which keep it current.
+    live: Option<usize>,
 }
 
 impl<T, const L: usize> SubGrid<T, L> where T: Default + Clone {
-    pub const L_I: isize = L as isize;
-
     fn new() -> SubGrid<T, L> {
-        SubGrid {
-            values: allocate_2d(),
-        }
+        SubGrid { live: Some(0), ..SubGrid::from_chunk(Chunk::new()) }
+    }
+
+    fn from_chunk(values: Chunk<T, L>) -> SubGrid<T, L> {
+        SubGrid::from_shared(Arc::new(values))
+    }
+
+    fn from_shared(values: Arc<Chunk<T, L>>) -> SubGrid<T, L> {
+        SubGrid { values, hash: None, live: None }
     }
 
     fn get(&self, p: &SubGridPoint) -> &T {
@@ -471,7 +703,14 @@ impl<T, const L: usize> SubGrid<T, L> where T: Default + Clone {
     }
 
     fn set(&mut self, p: &SubGridPoint, v: T) {
-        self.values[p.x][p.z] = v;
+        self.chunk_mut()[p.x][p.z] = v;
+    }
+
+    // Copies the chunk first if anything else still shares it.
+    fn chunk_mut(&mut self) -> &mut Chunk<T, L> {
+        self.hash = None;
+        self.live = None;
+        Arc::make_mut(&mut self.values)
     }
 }
 
@@ -494,22 +733,73 @@ pub fn div_neg_isize_3(a: isize, b: isize) -> isize {
     (a / b) + ((a % b) >> 31)
 }
 
-fn allocate_2d<T, const L: usize>() -> [[T; L]; L] where T: Default {
-    let mut values: [[MaybeUninit<T>; L]; L] = unsafe {
-        MaybeUninit::uninit().assume_init()
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::LifeRule;
+
+    // Sets each cell whose right-hand neighbour is live.
+    fn copy_from_right(grid: &mut Grid<u8, 4>) {
+        let right = vec![Offset::new(1, 0)];
+        grid.tick(|_| &right, |p, _, neighbors| match neighbors[0] {
+            Some(v) if *v != 0 => vec![Update::new(p.copy(), |_| Some(1))],
+            _ => Vec::new(),
+        });
+    }
+
+    #[test]
+    fn scan_reads_neighbours_across_chunk_edges() {
+        let mut grid: Grid<u8, 4> = Grid::new();
+        // The first column of chunk (0, 0), and the first column of chunk (1, 0) one row down.
+        grid.set(&Point::new(0, 1), 1);
+        grid.set(&Point::new(4, 2), 1);
+
+        copy_from_right(&mut grid);
+
+        // (3, 1)'s neighbour is (4, 1), in the next chunk and dead. A scan whose range ran to
+        // `start + L` took it for a cell of its own chunk and read (0, 1) instead.
+        assert_eq!(grid.get(&Point::new(3, 1)), Some(&0));
+        // Likewise (3, 2) read the dead (0, 2) rather than the live (4, 2).
+        assert_eq!(grid.get(&Point::new(3, 2)), Some(&1));
+        assert_eq!(grid.get(&Point::new(-1, 1)), Some(&1));
+    }
 
-    unsafe {
-        for col in &mut values[..] {
-            let mut row: [MaybeUninit<T>; L] = MaybeUninit::uninit().assume_init();
+    fn block_with_empty_generator() -> Grid<u8, 4> {
+        let mut grid = Grid::new();
+        for (x, z) in [(1, 1), (1, 2), (2, 1), (2, 2)] {
+            grid.set(&Point::new(x, z), 1);
+        }
+        grid.set_generator(|_| Chunk::new());
+        grid
+    }
 
-            for col in &mut row[..] {
-                *col = MaybeUninit::new(T::default());
-            }
+    #[test]
+    fn generator_stops_growing_around_a_still_life() {
+        let rule = LifeRule::conway();
+        let mut grid = block_with_empty_generator();
 
-            *col = row;
+        // The first tick generates the ring around everything; nothing is written after that.
+        assert_eq!(grid.step(&rule).subgrids, 9);
+        for _ in 0..20 {
+            assert_eq!(grid.step(&rule).subgrids, 9);
         }
     }
 
-    unsafe { std::mem::transmute_copy(&values) }
+    #[test]
+    fn generator_follows_writes() {
+        let rule = LifeRule::conway();
+        let mut grid = block_with_empty_generator();
+        grid.step(&rule);
+
+        // A lone cell far off allocates its own subgrid; the next tick generates the ring around
+        // it, and the tick after that only what the dying cell's subgrid touched.
+        grid.set(&Point::new(41, 41), 1);
+        assert_eq!(grid.step(&rule).subgrids, 18);
+        assert_eq!(grid.step(&rule).subgrids, 18);
+
+        // Bulk writes lose track of what changed, so the ring around everything is generated.
+        grid.iter_mut().for_each(|_| {});
+        // That widens both 3x3 groups of subgrids to 5x5.
+        assert_eq!(grid.step(&rule).subgrids, 25 + 25);
+    }
 }
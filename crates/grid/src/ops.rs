@@ -0,0 +1,194 @@
+use std::fmt::Display;
+use std::sync::Arc;
+
+use crate::chunk::Chunk;
+use crate::{Grid, SubGrid, SubGridIndex};
+
+// Set operations treat a cell as a member when it differs from `T::default()`, and take a
+// member's value from whichever grid it is a member of, `self` first. Chunks of the result that
+// come out entirely default are not kept.
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display + PartialEq {
+    /// Cells live in either grid.
+    pub fn union(&self, other: &Grid<T, L>) -> Grid<T, L> {
+        self.combine(other, true, true, |a, b, default| match *a != *default {
+            true => a.clone(),
+            false => b.clone(),
+        })
+    }
+
+    /// Cells live in both grids.
+    pub fn intersection(&self, other: &Grid<T, L>) -> Grid<T, L> {
+        self.combine(other, false, false, |a, b, default| match *b != *default {
+            true => a.clone(),
+            false => T::default(),
+        })
+    }
+
+    /// Cells live in `self` but not in `other`.
+    pub fn difference(&self, other: &Grid<T, L>) -> Grid<T, L> {
+        self.combine(other, true, false, |a, b, default| match *b != *default {
+            true => T::default(),
+            false => a.clone(),
+        })
+    }
+
+    /// Cells live in exactly one of the grids, such as those that changed between two generations
+    /// of a two-state rule.
+    pub fn xor(&self, other: &Grid<T, L>) -> Grid<T, L> {
+        self.combine(other, true, true, |a, b, default| match (*a != *default, *b != *default) {
+            (true, false) => a.clone(),
+            (false, true) => b.clone(),
+            _ => T::default(),
+        })
+    }
+
+    // Chunks present on one side only are copied if `keep_self` or `keep_other` allows it, and
+    // `f` only runs where both sides have the chunk.
+    fn combine<F: Fn(&T, &T, &T) -> T>(&self, other: &Grid<T, L>, keep_self: bool, keep_other: bool, f: F) -> Grid<T, L> {
+        let default = T::default();
+        let mut r = Grid::new();
+
+        for (index, sub) in &self.values {
+            let chunk = match other.values.get(index) {
+                Some(o) => Arc::new(Chunk::from_fn(|x, z| f(&sub.values[x][z], &o.values[x][z], &default))),
+                None if keep_self => sub.values.clone(),
+                None => continue,
+            };
+
+            r.insert_chunk(index, chunk, &default);
+        }
+
+        if keep_other {
+            for (index, sub) in &other.values {
+                if !self.values.contains_key(index) {
+                    r.insert_chunk(index, sub.values.clone(), &default);
+                }
+            }
+        }
+
+        r
+    }
+
+    // Takes the chunk shared, so that chunks copied over from either side are not duplicated.
+    fn insert_chunk(&mut self, index: &SubGridIndex, chunk: Arc<Chunk<T, L>>, default: &T) {
+        if chunk.as_slice().iter().any(|v| v != default) {
+            self.values.insert(index.copy(), SubGrid::from_shared(chunk));
+        }
+    }
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
+    /// Combines the two grids cell by cell. `f` sees `T::default()` or `U::default()` for cells in
+    /// a chunk only one grid has, and is not called where neither has one. Chunks of the result
+    /// that are entirely `V::default()` are not kept.
+    pub fn zip_with<U, V, F>(&self, other: &Grid<U, L>, f: F) -> Grid<V, L>
+        where U: Default + Clone + Display,
+              V: Default + Clone + Display + PartialEq,
+              F: Fn(&T, &U) -> V,
+    {
+        let default = V::default();
+        let (self_default, other_default) = (T::default(), U::default());
+        let mut r = Grid::new();
+
+        for (index, sub) in &self.values {
+            let chunk = match other.values.get(index) {
+                Some(o) => Chunk::from_fn(|x, z| f(&sub.values[x][z], &o.values[x][z])),
+                None => Chunk::from_fn(|x, z| f(&sub.values[x][z], &other_default)),
+            };
+
+            r.insert_chunk(index, Arc::new(chunk), &default);
+        }
+
+        for (index, sub) in &other.values {
+            if !self.values.contains_key(index) {
+                r.insert_chunk(index, Arc::new(Chunk::from_fn(|x, z| f(&self_default, &sub.values[x][z]))), &default);
+            }
+        }
+
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Point;
+
+    fn grid(cells: &[(isize, isize)]) -> Grid<u8, 4> {
+        let mut grid = Grid::new();
+        for (x, z) in cells {
+            grid.set(&Point::new(*x, *z), 1);
+        }
+        grid
+    }
+
+    fn live<T: Default + Clone + Display + PartialEq>(grid: &Grid<T, 4>) -> Vec<(isize, isize)> {
+        let default = T::default();
+        let mut r: Vec<(isize, isize)> = grid.iter().filter(|(_, v)| **v != default).map(|(p, _)| (p.x, p.z)).collect();
+        r.sort_unstable();
+        r
+    }
+
+    fn shares(a: &Grid<u8, 4>, b: &Grid<u8, 4>, x: isize, z: isize) -> bool {
+        let index = SubGridIndex::new(x, z);
+        Arc::ptr_eq(&a.values[&index].values, &b.values[&index].values)
+    }
+
+    #[test]
+    fn chunks_on_one_side() {
+        // Both have chunk (0, 0); only `a` has (-1, 0) and only `b` has (2, 2).
+        let a = grid(&[(0, 0), (1, 1), (-3, 0)]);
+        let b = grid(&[(1, 1), (2, 0), (10, 10)]);
+
+        assert_eq!(live(&a.union(&b)), vec![(-3, 0), (0, 0), (1, 1), (2, 0), (10, 10)]);
+        assert_eq!(live(&a.intersection(&b)), vec![(1, 1)]);
+        assert_eq!(live(&a.difference(&b)), vec![(-3, 0), (0, 0)]);
+        assert_eq!(live(&b.difference(&a)), vec![(2, 0), (10, 10)]);
+        assert_eq!(live(&a.xor(&b)), vec![(-3, 0), (0, 0), (2, 0), (10, 10)]);
+
+        assert_eq!(a.intersection(&b).values.len(), 1);
+        assert_eq!(a.difference(&b).values.len(), 2);
+    }
+
+    #[test]
+    fn empty_chunks_are_dropped() {
+        let a = grid(&[(0, 0), (5, 5)]);
+        let b = grid(&[(1, 1), (6, 6)]);
+
+        assert!(a.intersection(&b).values.is_empty());
+        assert!(a.xor(&a).values.is_empty());
+        assert!(a.difference(&a).values.is_empty());
+
+        // A chunk holding only default cells is not carried over either.
+        let mut c = grid(&[(9, 9)]);
+        c.set(&Point::new(-9, -9), 0);
+        assert_eq!(c.union(&Grid::new()).values.len(), 1);
+    }
+
+    #[test]
+    fn one_sided_chunks_stay_shared() {
+        let a = grid(&[(0, 0), (-3, 0)]);
+        let b = grid(&[(1, 1), (10, 10)]);
+
+        let union = a.union(&b);
+        assert!(shares(&union, &a, -1, 0));
+        assert!(shares(&union, &b, 2, 2));
+        assert!(!shares(&union, &a, 0, 0));
+        assert!(shares(&a.difference(&b), &a, -1, 0));
+        assert!(shares(&a.xor(&b), &b, 2, 2));
+    }
+
+    #[test]
+    fn zip_with_sees_defaults_for_missing_chunks() {
+        let a = grid(&[(0, 0), (-3, 0)]);
+        let mut b: Grid<bool, 4> = Grid::new();
+        b.set(&Point::new(0, 0), true);
+        b.set(&Point::new(10, 10), true);
+
+        let r: Grid<u16, 4> = a.zip_with(&b, |v, w| *v as u16 + 2 * *w as u16);
+        assert_eq!(r.get(&Point::new(0, 0)), Some(&3));
+        assert_eq!(r.get(&Point::new(-3, 0)), Some(&1));
+        assert_eq!(r.get(&Point::new(10, 10)), Some(&2));
+        assert_eq!(live(&r), vec![(-3, 0), (0, 0), (10, 10)]);
+    }
+}
@@ -0,0 +1,390 @@
+use std::fmt::Display;
+use std::hash::Hash;
+use std::io::{Read, Write};
+
+use crate::chunk::Chunk;
+use crate::snapshot::{read_array, CellCodec, Crc32Reader, Crc32Writer};
+use crate::{Grid, GridError, SubGrid, SubGridIndex};
+
+// Layout, all integers little-endian:
+//
+//   magic "GPCH" | version u16 | L u32 | base hash u64 | target hash u64 | generation u64
+//   | change count u64 | changes (x i64, z i64, tag u8, body) | crc32 u32
+//
+// Bodies by tag: 0 removed, none; 1 replaced, L*L cells in x-major order; 2 cells, count u32 then
+// (offset, cell) pairs, where the offset is x * L + z as a u16, or a u32 if L * L is above 65536.
+
+const MAGIC: &[u8; 4] = b"GPCH";
+const VERSION: u16 = 1;
+
+/// How one chunk differs between two grids.
+#[derive(Clone)]
+pub enum ChunkChange<T, const L: usize> {
+    /// The chunk is not allocated in the target.
+    Removed,
+    /// Every cell of the chunk, used when most of them changed.
+    Replaced(Chunk<T, L>),
+    /// The cells that changed, by position within the chunk. The chunk is allocated first if the
+    /// base lacks it.
+    Cells(Vec<(usize, usize, T)>),
+}
+
+/// The changes that turn one grid into another, made by [`Grid::diff`] and applied with
+/// [`Grid::apply`]. A patch carries the hashes of both states, so a copy that has drifted from the
+/// base is refused rather than silently corrupted.
+///
+/// The hashes cover live cells only: allocated chunks holding nothing but `T::default()` do not
+/// change them. A grid that differs from the base only in such chunks still accepts the patch,
+/// and may keep or lack empty chunks the target has.
+#[derive(Clone)]
+pub struct GridPatch<T, const L: usize> {
+    base_hash: u64,
+    target_hash: u64,
+    generation: u64,
+    changes: Vec<(SubGridIndex, ChunkChange<T, L>)>,
+}
+
+impl<T, const L: usize> GridPatch<T, L> where T: Default + Clone {
+    /// The hash of the grid the patch applies to, as `Grid::cycle_hash().positional`.
+    pub fn base_hash(&self) -> u64 {
+        self.base_hash
+    }
+
+    /// The hash the grid has once the patch is applied.
+    pub fn target_hash(&self) -> u64 {
+        self.target_hash
+    }
+
+    /// The target's generation.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn changes(&self) -> &[(SubGridIndex, ChunkChange<T, L>)] {
+        &self.changes
+    }
+
+    /// True if the patch changes no cells, though it may still advance the generation.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn write<W: Write, C: CellCodec<T>>(&self, w: W, codec: &C) -> Result<(), GridError> {
+        let mut w = Crc32Writer::new(w);
+
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&(L as u32).to_le_bytes())?;
+        w.write_all(&self.base_hash.to_le_bytes())?;
+        w.write_all(&self.target_hash.to_le_bytes())?;
+        w.write_all(&self.generation.to_le_bytes())?;
+        w.write_all(&(self.changes.len() as u64).to_le_bytes())?;
+
+        for (index, change) in &self.changes {
+            w.write_all(&(index.x as i64).to_le_bytes())?;
+            w.write_all(&(index.z as i64).to_le_bytes())?;
+
+            match change {
+                ChunkChange::Removed => w.write_all(&[0])?,
+                ChunkChange::Replaced(chunk) => {
+                    w.write_all(&[1])?;
+                    for v in chunk.as_slice() {
+                        codec.write_cell(&mut w, v)?;
+                    }
+                }
+                ChunkChange::Cells(cells) => {
+                    w.write_all(&[2])?;
+                    w.write_all(&(cells.len() as u32).to_le_bytes())?;
+                    for (x, z, v) in cells {
+                        match wide_offsets::<L>() {
+                            true => w.write_all(&((x * L + z) as u32).to_le_bytes())?,
+                            false => w.write_all(&((x * L + z) as u16).to_le_bytes())?,
+                        }
+                        codec.write_cell(&mut w, v)?;
+                    }
+                }
+            }
+        }
+
+        let crc = w.crc();
+        let mut w = w.into_inner();
+        w.write_all(&crc.to_le_bytes())?;
+        w.flush()?;
+
+        Ok(())
+    }
+
+    pub fn read<R: Read, C: CellCodec<T>>(r: R, codec: &C) -> Result<GridPatch<T, L>, GridError> {
+        let mut r = Crc32Reader::new(r);
+
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(GridError::ParseError("not a grid patch".to_string()));
+        }
+
+        let version = u16::from_le_bytes(read_array(&mut r)?);
+        if version != VERSION {
+            return Err(GridError::ParseError(format!("unsupported patch version {}", version)));
+        }
+
+        let l = u32::from_le_bytes(read_array(&mut r)?);
+        if l as usize != L {
+            return Err(GridError::ParseError(format!("patch has subgrid size {}, expected {}", l, L)));
+        }
+
+        let base_hash = u64::from_le_bytes(read_array(&mut r)?);
+        let target_hash = u64::from_le_bytes(read_array(&mut r)?);
+        let generation = u64::from_le_bytes(read_array(&mut r)?);
+        let count = u64::from_le_bytes(read_array(&mut r)?);
+
+        let mut changes = Vec::new();
+        for _ in 0..count {
+            let x = i64::from_le_bytes(read_array(&mut r)?) as isize;
+            let z = i64::from_le_bytes(read_array(&mut r)?) as isize;
+
+            let change = match read_array(&mut r)? {
+                [0] => ChunkChange::Removed,
+                [1] => {
+                    let mut chunk = Chunk::new();
+                    for v in chunk.as_mut_slice() {
+                        *v = codec.read_cell(&mut r)?;
+                    }
+                    ChunkChange::Replaced(chunk)
+                }
+                [2] => {
+                    let n = u32::from_le_bytes(read_array(&mut r)?) as usize;
+                    let mut cells = Vec::with_capacity(n.min(L * L));
+
+                    for _ in 0..n {
+                        let offset = match wide_offsets::<L>() {
+                            true => u32::from_le_bytes(read_array(&mut r)?) as usize,
+                            false => u16::from_le_bytes(read_array(&mut r)?) as usize,
+                        };
+                        if offset >= L * L {
+                            return Err(GridError::ParseError(format!("cell offset {} is outside a chunk", offset)));
+                        }
+
+                        cells.push((offset / L, offset % L, codec.read_cell(&mut r)?));
+                    }
+
+                    ChunkChange::Cells(cells)
+                }
+                [tag] => return Err(GridError::ParseError(format!("unknown chunk change {}", tag))),
+            };
+
+            changes.push((SubGridIndex::new(x, z), change));
+        }
+
+        let found = r.crc();
+        let expected = u32::from_le_bytes(read_array(&mut r.into_inner())?);
+        if expected != found {
+            return Err(GridError::ChecksumMismatch { expected: expected.into(), found: found.into() });
+        }
+
+        Ok(GridPatch { base_hash, target_hash, generation, changes })
+    }
+}
+
+fn wide_offsets<const L: usize>() -> bool {
+    L * L > 1 << 16
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display + PartialEq + Hash {
+    /// The patch that turns this grid into `target`. Chunks are sent whole once more than a
+    /// quarter of their cells changed.
+    pub fn diff(&self, target: &Grid<T, L>) -> GridPatch<T, L> {
+        let default = T::default();
+        let mut changes = Vec::new();
+
+        for index in self.values.keys() {
+            if !target.values.contains_key(index) {
+                changes.push((index.copy(), ChunkChange::Removed));
+            }
+        }
+
+        for (index, sub) in &target.values {
+            let base = self.values.get(index);
+
+            let mut cells = Vec::new();
+            for (x, row) in sub.values.rows().enumerate() {
+                for (z, v) in row.iter().enumerate() {
+                    let old = base.map_or(&default, |b| &b.values[x][z]);
+                    if old != v {
+                        cells.push((x, z, v.clone()));
+                    }
+                }
+            }
+
+            let change = match (base.is_some(), cells.len()) {
+                (true, 0) => continue,
+                (_, n) if n > L * L / 4 => ChunkChange::Replaced((*sub.values).clone()),
+                _ => ChunkChange::Cells(cells),
+            };
+
+            changes.push((index.copy(), change));
+        }
+
+        GridPatch {
+            base_hash: self.cycle_hash_uncached().positional,
+            target_hash: target.cycle_hash_uncached().positional,
+            generation: target.generation,
+            changes,
+        }
+    }
+
+    /// Applies a patch made against this grid's current state, and takes the target's generation.
+    /// Fails without changing anything if the grid's hash is not the patch's base hash, or if the
+    /// patched cells do not hash to the patch's target hash.
+    pub fn apply(&mut self, patch: &GridPatch<T, L>) -> Result<(), GridError> {
+        let found = self.cycle_hash().positional;
+        if found != patch.base_hash {
+            return Err(GridError::StateMismatch { expected: patch.base_hash, found });
+        }
+
+        // Chunks are shared, so patching a copy of the map costs only the chunks it writes.
+        let mut values = self.values.clone();
+
+        for (index, change) in &patch.changes {
+            match change {
+                ChunkChange::Removed => {
+                    values.remove(index);
+                }
+                ChunkChange::Replaced(chunk) => {
+                    values.insert(index.copy(), SubGrid::from_chunk(chunk.clone()));
+                }
+                ChunkChange::Cells(cells) => {
+                    let chunk = values.entry(index.copy()).or_insert_with(SubGrid::new).chunk_mut();
+                    for (x, z, v) in cells {
+                        chunk[*x][*z] = v.clone();
+                    }
+                }
+            }
+        }
+
+        let base = std::mem::replace(&mut self.values, values);
+        let found = self.cycle_hash().positional;
+        if found != patch.target_hash {
+            self.values = base;
+            return Err(GridError::ChecksumMismatch { expected: patch.target_hash, found });
+        }
+
+        if !patch.changes.is_empty() {
+            self.invalidate_subgrids_to_scan();
+            self.untracked_write();
+        }
+        self.generation = patch.generation;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::PrimitiveCodec;
+    use crate::Point;
+
+    fn cells(grid: &Grid<u16, 4>) -> Vec<(isize, isize, u16)> {
+        let mut r: Vec<(isize, isize, u16)> = grid.iter().filter(|(_, v)| **v != 0).map(|(p, v)| (p.x, p.z, *v)).collect();
+        r.sort_unstable();
+        r
+    }
+
+    // A base and a target that between them need every kind of change.
+    fn pair() -> (Grid<u16, 4>, Grid<u16, 4>) {
+        let mut base = Grid::new();
+        let mut target = Grid::new();
+
+        // Removed.
+        base.set(&Point::new(-5, -5), 1);
+        // Cells: one changed, one cleared, one added.
+        base.set(&Point::new(0, 0), 2);
+        base.set(&Point::new(1, 1), 3);
+        target.set(&Point::new(0, 0), 4);
+        target.set(&Point::new(2, 3), 5);
+        // Replaced, in a chunk the base lacks.
+        for x in 8..12 {
+            for z in 0..4 {
+                target.set(&Point::new(x, z), (x * 4 + z) as u16);
+            }
+        }
+        target.generation = 7;
+
+        (base, target)
+    }
+
+    #[test]
+    fn apply_reaches_the_target() {
+        let (mut base, mut target) = pair();
+        let patch = base.diff(&target);
+
+        assert!(patch.changes().iter().any(|(_, c)| matches!(c, ChunkChange::Removed)));
+        assert!(patch.changes().iter().any(|(_, c)| matches!(c, ChunkChange::Cells(_))));
+        assert!(patch.changes().iter().any(|(_, c)| matches!(c, ChunkChange::Replaced(_))));
+
+        base.apply(&patch).unwrap();
+        assert_eq!(cells(&base), cells(&target));
+        assert_eq!(base.generation(), 7);
+        assert_eq!(base.cycle_hash().positional, target.cycle_hash().positional);
+    }
+
+    #[test]
+    fn round_trip() {
+        let (mut base, target) = pair();
+        let patch = base.diff(&target);
+
+        let mut buf = Vec::new();
+        patch.write(&mut buf, &PrimitiveCodec).unwrap();
+        let read = GridPatch::<u16, 4>::read(&buf[..], &PrimitiveCodec).unwrap();
+
+        assert_eq!((read.base_hash(), read.target_hash(), read.generation()), (patch.base_hash(), patch.target_hash(), 7));
+        assert_eq!(read.changes().len(), patch.changes().len());
+
+        base.apply(&read).unwrap();
+        assert_eq!(cells(&base), cells(&target));
+    }
+
+    #[test]
+    fn rejects_a_drifted_base() {
+        let (mut base, target) = pair();
+        let patch = base.diff(&target);
+
+        base.set(&Point::new(1, 1), 9);
+        let before = cells(&base);
+
+        assert!(matches!(base.apply(&patch), Err(GridError::StateMismatch { .. })));
+        assert_eq!(cells(&base), before);
+    }
+
+    #[test]
+    fn rejects_a_wrong_target() {
+        let (mut base, target) = pair();
+        let mut patch = base.diff(&target);
+        let before = cells(&base);
+
+        // The changes no longer produce the state the patch names.
+        patch.changes.retain(|(_, c)| !matches!(c, ChunkChange::Removed));
+
+        assert!(matches!(base.apply(&patch), Err(GridError::ChecksumMismatch { .. })));
+        assert_eq!(cells(&base), before);
+        assert_eq!(base.generation(), 0);
+    }
+
+    #[test]
+    fn empty_chunks_do_not_change_the_hash() {
+        let (base, target) = pair();
+        let patch = base.diff(&target);
+
+        // An allocated chunk of default cells hashes like no chunk at all, so the patch applies.
+        let mut padded = Grid::new();
+        for (x, z, v) in cells(&base) {
+            padded.set(&Point::new(x, z), v);
+        }
+        padded.set(&Point::new(40, 40), 0);
+        assert_eq!(padded.cycle_hash().positional, patch.base_hash());
+
+        padded.apply(&patch).unwrap();
+        assert_eq!(cells(&padded), cells(&target));
+    }
+}
@@ -0,0 +1,343 @@
+use std::fmt::Display;
+
+use rustc_hash::FxHashMap;
+
+use crate::{Grid, GridError, Point, SubGrid, SubGridIndex};
+
+/// One of the eight rotations and reflections of the square. Rotations turn x towards z.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Transform {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Negates x.
+    FlipX,
+    /// Negates z.
+    FlipZ,
+    /// Swaps x and z.
+    Transpose,
+    /// Swaps x and z and negates both.
+    AntiTranspose,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::FlipX,
+        Transform::FlipZ,
+        Transform::Transpose,
+        Transform::AntiTranspose,
+    ];
+
+    /// Maps a point about the origin.
+    pub fn apply(&self, x: isize, z: isize) -> (isize, isize) {
+        match self {
+            Transform::Identity => (x, z),
+            Transform::Rotate90 => (-z, x),
+            Transform::Rotate180 => (-x, -z),
+            Transform::Rotate270 => (z, -x),
+            Transform::FlipX => (-x, z),
+            Transform::FlipZ => (x, -z),
+            Transform::Transpose => (z, x),
+            Transform::AntiTranspose => (-z, -x),
+        }
+    }
+
+    fn swaps_axes(&self) -> bool {
+        matches!(self, Transform::Rotate90 | Transform::Rotate270 | Transform::Transpose | Transform::AntiTranspose)
+    }
+}
+
+/// How `Grid::paste` combines a pattern cell with the cell already there. A cell is live when it
+/// differs from `T::default()`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Blend {
+    /// Every cell of the pattern replaces the grid's, dead ones included.
+    Overwrite,
+    /// Only live cells of the pattern are written.
+    SkipDefault,
+    /// Live grid cells are kept; the pattern fills in the dead ones.
+    Or,
+    /// Cells live in both become dead; otherwise whichever is live wins.
+    Xor,
+}
+
+/// A rectangle of cells detached from any grid, `width` along x and `height` along z.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern<T> {
+    width: usize,
+    height: usize,
+    // x-major, like Chunk.
+    cells: Vec<T>,
+}
+
+impl<T> Pattern<T> where T: Default + Clone {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![T::default(); width * height],
+        }
+    }
+
+    /// The smallest pattern holding `cells`, with the minimum corner of their bounding box moved to
+    /// the origin.
+    pub fn from_cells<I: IntoIterator<Item=(Point, T)>>(cells: I) -> Self {
+        let cells: Vec<(Point, T)> = cells.into_iter().collect();
+        if cells.is_empty() {
+            return Self::new(0, 0);
+        }
+
+        let min_x = cells.iter().map(|(p, _)| p.x).min().unwrap();
+        let min_z = cells.iter().map(|(p, _)| p.z).min().unwrap();
+        let max_x = cells.iter().map(|(p, _)| p.x).max().unwrap();
+        let max_z = cells.iter().map(|(p, _)| p.z).max().unwrap();
+
+        let mut r = Self::new((max_x - min_x + 1) as usize, (max_z - min_z + 1) as usize);
+        for (p, v) in cells {
+            r.set((p.x - min_x) as usize, (p.z - min_z) as usize, v);
+        }
+
+        r
+    }
+
+    /// Reads the plaintext format: one line per z, `O` or `*` for `alive` and `.` for a dead cell.
+    /// Lines starting with `!` are comments; trailing whitespace is ignored and short lines are
+    /// padded with dead cells. Any other character is an error.
+    pub fn parse_plaintext(text: &str, alive: T) -> Result<Self, GridError> {
+        let rows: Vec<(usize, &str)> = text.lines()
+            .enumerate()
+            .filter(|(_, l)| !l.starts_with('!'))
+            .map(|(i, l)| (i + 1, l.trim_end()))
+            .collect();
+        let width = rows.iter().map(|(_, r)| r.chars().count()).max().unwrap_or(0);
+
+        let mut r = Self::new(width, rows.len());
+        for (z, (line, row)) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                match c {
+                    'O' | '*' => r.set(x, z, alive.clone()),
+                    '.' => {}
+                    _ => return Err(GridError::ParseError(format!("unexpected '{}' at line {}, column {}", c, line, x + 1))),
+                }
+            }
+        }
+
+        Ok(r)
+    }
+
+    /// Writes the plaintext format, `O` for cells where `is_set` holds and `.` elsewhere.
+    pub fn to_plaintext<F: Fn(&T) -> bool>(&self, is_set: F) -> String {
+        let mut s = String::with_capacity((self.width + 1) * self.height);
+        for z in 0..self.height {
+            for x in 0..self.width {
+                s.push(if is_set(&self.cells[x * self.height + z]) { 'O' } else { '.' });
+            }
+            s.push('\n');
+        }
+
+        s
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, z: usize) -> Option<&T> {
+        if x >= self.width || z >= self.height {
+            return None;
+        }
+
+        self.cells.get(x * self.height + z)
+    }
+
+    /// # Panics
+    ///
+    /// If `(x, z)` is outside the pattern.
+    pub fn set(&mut self, x: usize, z: usize, v: T) {
+        assert!(x < self.width && z < self.height, "({}, {}) is outside a {}x{} pattern", x, z, self.width, self.height);
+        self.cells[x * self.height + z] = v;
+    }
+
+    /// Every cell with its position in the pattern.
+    pub fn iter(&self) -> impl Iterator<Item=((usize, usize), &T)> + '_ {
+        let height = self.height.max(1);
+        self.cells.iter().enumerate().map(move |(i, v)| ((i / height, i % height), v))
+    }
+
+    /// The pattern under `transform`, moved back so its corner is at the origin.
+    pub fn transformed(&self, transform: Transform) -> Self {
+        let (width, height) = match transform.swaps_axes() {
+            true => (self.height, self.width),
+            false => (self.width, self.height),
+        };
+
+        // Each axis of the image is spanned by 0 and the image of the far corner.
+        let (ax, az) = transform.apply(self.width as isize - 1, self.height as isize - 1);
+        let (ox, oz) = (ax.min(0), az.min(0));
+
+        let mut r = Self::new(width, height);
+        for ((x, z), v) in self.iter() {
+            let (tx, tz) = transform.apply(x as isize, z as isize);
+            r.set((tx - ox) as usize, (tz - oz) as usize, v.clone());
+        }
+
+        r
+    }
+
+    /// A grid holding the pattern with its corner at the origin.
+    pub fn to_grid<const L: usize>(&self) -> Grid<T, L> where T: Display + PartialEq {
+        let mut grid = Grid::new();
+        grid.paste(self, &Point::new(0, 0), Transform::Identity, Blend::SkipDefault);
+        grid
+    }
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display + PartialEq {
+    /// Copies the rectangle from `start` to `end`, inclusive. Unallocated cells come out as
+    /// `T::default()`.
+    pub fn crop(&self, start: &Point, end: &Point) -> Result<Pattern<T>, GridError> {
+        if end.x < start.x || end.z < start.z {
+            return Err(GridError::InvalidArgument(format!("crop rectangle from {} to {} is empty", start, end)));
+        }
+
+        let mut r = Pattern::new((end.x - start.x + 1) as usize, (end.z - start.z + 1) as usize);
+        for (p, v) in self.iter_rect(start, end) {
+            r.set((p.x - start.x) as usize, (p.z - start.z) as usize, v.clone());
+        }
+
+        Ok(r)
+    }
+
+    /// Writes `pattern`, after `transform`, with its corner at `at`.
+    pub fn paste(&mut self, pattern: &Pattern<T>, at: &Point, transform: Transform, blend: Blend) {
+        let transformed;
+        let pattern = match transform {
+            Transform::Identity => pattern,
+            _ => {
+                transformed = pattern.transformed(transform);
+                &transformed
+            }
+        };
+        let default = T::default();
+
+        for ((x, z), v) in pattern.iter() {
+            let p = Point::new(at.x + x as isize, at.z + z as isize);
+            let live = *v != default;
+
+            let new = match blend {
+                Blend::Overwrite => Some(v.clone()),
+                Blend::SkipDefault => match live {
+                    true => Some(v.clone()),
+                    false => None,
+                },
+                Blend::Or => match (self.get(&p).is_some_and(|c| *c != default), live) {
+                    (false, true) => Some(v.clone()),
+                    _ => None,
+                },
+                Blend::Xor => match (self.get(&p).is_some_and(|c| *c != default), live) {
+                    (true, true) => Some(T::default()),
+                    (false, true) => Some(v.clone()),
+                    _ => None,
+                },
+            };
+
+            if let Some(new) = new {
+                self.set(&p, new);
+            }
+        }
+    }
+
+    /// Rotates or reflects the whole grid about the origin. Only cells that differ from
+    /// `T::default()` are kept, so chunks left empty are released. Chunks the moved cells land in
+    /// start out as `T::default()`; the generator is not consulted.
+    pub fn transform(&mut self, transform: Transform) {
+        let default = T::default();
+        let mut values: FxHashMap<SubGridIndex, SubGrid<T, L>> = FxHashMap::default();
+
+        for (p, v) in self.iter().filter(|(_, v)| **v != default) {
+            let (x, z) = transform.apply(p.x, p.z);
+            let p = Point::new(x, z);
+
+            values.entry(p.to_subgrid_index(Grid::<T, L>::L_I))
+                .or_insert_with(SubGrid::new)
+                .set(&p.to_subgrid_point(Grid::<T, L>::L_I), v.clone());
+        }
+
+        self.values = values;
+        self.invalidate_subgrids_to_scan();
+        self.untracked_write();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+
+    #[test]
+    fn transform_moves_cells() {
+        let mut grid: Grid<u8, 4> = Grid::new();
+        grid.set(&Point::new(1, 0), 1);
+        grid.set(&Point::new(5, -2), 2);
+
+        grid.transform(Transform::Rotate90);
+
+        let mut cells: Vec<(Point, u8)> = grid.iter().filter(|(_, v)| **v != 0).map(|(p, v)| (p, *v)).collect();
+        cells.sort_by_key(|(p, _)| (p.x, p.z));
+        assert_eq!(cells, vec![(Point::new(0, 1), 1), (Point::new(2, 5), 2)]);
+    }
+
+    #[test]
+    fn transform_does_not_generate() {
+        let mut grid: Grid<u8, 4> = Grid::new();
+        grid.set(&Point::new(1, 0), 1);
+        grid.set_generator(|_| {
+            let mut chunk = Chunk::new();
+            chunk.as_mut_slice().fill(7);
+            chunk
+        });
+
+        grid.transform(Transform::Rotate90);
+
+        assert_eq!(grid.get(&Point::new(0, 1)), Some(&1));
+        assert_eq!(grid.iter().filter(|(_, v)| **v != 0).count(), 1);
+    }
+
+    #[test]
+    fn plaintext_round_trip() {
+        let text = "!Name: Glider\n.O.\n..O\nOOO\n";
+        let pattern = Pattern::parse_plaintext(text, 1u8).unwrap();
+
+        assert_eq!((pattern.width(), pattern.height()), (3, 3));
+        assert_eq!(pattern.to_plaintext(|v| *v != 0), ".O.\n..O\nOOO\n");
+    }
+
+    #[test]
+    fn plaintext_accepts_stars_and_trailing_whitespace() {
+        let pattern = Pattern::parse_plaintext("*.  \r\n.\n..*\t\n", 1u8).unwrap();
+
+        assert_eq!((pattern.width(), pattern.height()), (3, 3));
+        assert_eq!(pattern.to_plaintext(|v| *v != 0), "O..\n...\n..O\n");
+    }
+
+    #[test]
+    fn plaintext_rejects_other_characters() {
+        for text in ["O.x\n", "O\n. O\n", "!comment\n.O\no.\n"] {
+            assert!(matches!(Pattern::parse_plaintext(text, 1u8), Err(GridError::ParseError(_))), "{:?}", text);
+        }
+
+        match Pattern::parse_plaintext("!c\n.O\n.#\n", 1u8) {
+            Err(GridError::ParseError(s)) => assert!(s.contains("line 3, column 2"), "{}", s),
+            r => panic!("{:?}", r.map(|p| p.to_plaintext(|v| *v != 0))),
+        }
+    }
+}
@@ -0,0 +1,699 @@
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::hash::BuildHasherDefault;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use lru::LruCache;
+use rustc_hash::{FxHashMap, FxHashSet, FxHasher};
+
+use crate::snapshot::{read_bytes, CellCodec, Crc32};
+use crate::stats::TickReport;
+use crate::chunk::Chunk;
+use crate::{div_neg_isize_3, Grid, GridError, Offset, Point, SubGrid, SubGridIndex, Update};
+
+// Region file layout, all integers little-endian:
+//
+//   magic "GSRG" | version u16 | L u32 | region size R u32
+//   | R*R table entries (offset u64, len u32, crc32 u32), x-major, zero offset = absent
+//   | chunk blobs
+//
+// A rewritten chunk is always appended, and its table entry is written only after the blob, so a
+// crash mid-write leaves the entry pointing at the old blob, or at a new one its crc rejects if the
+// writes reached the disk out of order. Old blobs are not reclaimed.
+
+const REGION_MAGIC: &[u8; 4] = b"GSRG";
+const META_MAGIC: &[u8; 4] = b"GSMT";
+const VERSION: u16 = 1;
+const HEADER_LEN: u64 = 4 + 2 + 4 + 4;
+const ENTRY_LEN: u64 = 8 + 4 + 4;
+
+pub const DEFAULT_REGION_SIZE: usize = 32;
+
+// Region files kept open between chunk reads and writes.
+const OPEN_REGIONS: usize = 16;
+
+/// A grid whose subgrids live in region files under a directory, with at most `capacity` of them
+/// held in memory. Subgrids are paged in when `try_get`, `try_set` or `tick` touch them and written
+/// back when they are evicted, on [`PagedGrid::flush`], or when the grid is dropped.
+///
+/// Its methods mirror [`Grid`]'s fallible ones, except that reads take `&mut self` since they may
+/// page a subgrid in.
+pub struct PagedGrid<T, C, const L: usize> where T: Default + Clone + Display, C: CellCodec<T> {
+    store: RegionStore,
+    codec: C,
+
+    resident: LruCache<SubGridIndex, Resident<T, L>, BuildHasherDefault<FxHasher>>,
+    known: FxHashSet<SubGridIndex>,
+    // Live cells per subgrid, kept by `tick` and dropped by other writes, like `SubGrid::live`.
+    live: FxHashMap<SubGridIndex, usize>,
+    // Regions evicted subgrids were written to since the last flush.
+    unsynced: FxHashSet<RegionIndex>,
+
+    generation: u64,
+    rule_id: Option<String>,
+}
+
+struct Resident<T, const L: usize> where T: Default + Clone {
+    sub: SubGrid<T, L>,
+    dirty: bool,
+}
+
+impl<T, C, const L: usize> PagedGrid<T, C, L> where T: Default + Clone + Display, C: CellCodec<T> {
+    pub const L_I: isize = L as isize;
+
+    /// Opens or creates a world in `dir` with the default region size. `capacity` is raised to 9
+    /// if lower, since a tick needs a subgrid and its Moore ring in memory at once.
+    pub fn open<P: AsRef<Path>>(dir: P, capacity: usize, codec: C) -> Result<Self, GridError> {
+        PagedGrid::open_with_region_size(dir, capacity, DEFAULT_REGION_SIZE, codec)
+    }
+
+    pub fn open_with_region_size<P: AsRef<Path>>(dir: P, capacity: usize, region_size: usize, codec: C) -> Result<Self, GridError> {
+        let store = RegionStore::open(dir.as_ref(), L, region_size)?;
+        let known = store.scan()?;
+        let (generation, rule_id) = store.read_meta()?;
+
+        Ok(Self {
+            store,
+            codec,
+            resident: LruCache::with_hasher(capacity.max(9), BuildHasherDefault::default()),
+            known,
+            live: FxHashMap::default(),
+            unsynced: FxHashSet::default(),
+            generation,
+            rule_id,
+        })
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn rule_id(&self) -> Option<&str> {
+        self.rule_id.as_deref()
+    }
+
+    pub fn set_rule_id(&mut self, rule_id: Option<String>) {
+        self.rule_id = rule_id;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.resident.cap()
+    }
+
+    pub fn resident_len(&self) -> usize {
+        self.resident.len()
+    }
+
+    pub fn subgrid_count(&self) -> usize {
+        self.known.len()
+    }
+
+    //
+
+    pub fn try_get(&mut self, p: &Point) -> Result<Option<&T>, GridError> {
+        let index = p.to_subgrid_index(Self::L_I);
+        self.ensure_resident(&index)?;
+
+        Ok(self.resident.get(&index).map(|r| r.sub.get(&p.to_subgrid_point(Self::L_I))))
+    }
+
+    pub fn try_set(&mut self, p: &Point, v: T) -> Result<(), GridError> {
+        self.live.remove(&p.to_subgrid_index(Self::L_I));
+        self.write(p, v)
+    }
+
+    /// Same as [`Grid::tick`], paging subgrids in as the scan reaches them. Subgrids are scanned
+    /// in coordinate order so that neighbouring subgrids stay resident between steps. Subgrids
+    /// are never released, so `subgrids_freed` is always 0.
+    pub fn tick<'v, FVisit: Fn(&Point) -> &'v Vec<Offset>, FUpdate: Fn(&Point, Option<&T>, Vec<Option<&T>>) -> Vec<Update<T>>>(
+        &mut self,
+        visitor: FVisit,
+        updater: FUpdate,
+    ) -> Result<TickReport, GridError> where T: PartialEq {
+        let start = Instant::now();
+        let subgrids_before = self.known.len();
+        let default = T::default();
+
+        let mut to_scan = FxHashSet::default();
+        for sub_index in &self.known {
+            for neighbor in sub_index.moore_neighbors(1, true) {
+                to_scan.insert(neighbor);
+            }
+        }
+
+        let mut to_scan: Vec<SubGridIndex> = to_scan.into_iter().collect();
+        to_scan.sort_by_key(|i| (i.x, i.z));
+
+        let mut updates = Vec::new();
+
+        for sub_index in &to_scan {
+            for neighbor in sub_index.moore_neighbors(1, true) {
+                self.ensure_resident(&neighbor)?;
+            }
+
+            // Every known subgrid is scanned, so all of them are counted before the updates.
+            if let Some(r) = self.resident.peek(sub_index) {
+                if !self.live.contains_key(sub_index) {
+                    let n = r.sub.values.as_slice().iter().filter(|v| **v != default).count();
+                    self.live.insert(sub_index.copy(), n);
+                }
+            }
+
+            let resident = &self.resident;
+            Grid::<T, L>::scan_subgrid(sub_index, |i| resident.peek(i).map(|r| &r.sub), &visitor, &updater, &mut updates);
+        }
+
+        // As in `Grid::tick`, births and deaths compare each cell before the tick and after its
+        // latest update.
+        let mut touched: FxHashMap<Point, (bool, bool)> = FxHashMap::default();
+
+        for update in &updates {
+            let old = self.try_get(&update.p)?;
+            let was_live = old.is_some_and(|v| *v != default);
+            let new = (update.f)(old).unwrap_or_default();
+            let is_live = new != default;
+
+            touched.entry(update.p.copy()).or_insert((was_live, was_live)).1 = is_live;
+
+            self.write(&update.p, new)?;
+            let n = self.live.entry(update.p.to_subgrid_index(Self::L_I)).or_insert(0);
+            *n = *n + is_live as usize - was_live as usize;
+        }
+
+        self.generation += 1;
+
+        Ok(TickReport {
+            generation: self.generation,
+            births: touched.values().filter(|(before, after)| !*before && *after).count(),
+            deaths: touched.values().filter(|(before, after)| *before && !*after).count(),
+            population: self.live.values().sum(),
+            subgrids: self.known.len(),
+            active_subgrids: self.live.values().filter(|n| **n > 0).count(),
+            subgrids_allocated: self.known.len() - subgrids_before,
+            subgrids_freed: 0,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// Writes every dirty resident subgrid and the world metadata, then syncs the files to disk,
+    /// along with those written by evictions since the last flush.
+    pub fn flush(&mut self) -> Result<(), GridError> {
+        let mut touched = std::mem::take(&mut self.unsynced);
+
+        for (index, r) in self.resident.iter_mut() {
+            if r.dirty {
+                self.store.write_chunk(index, &r.sub, &self.codec)?;
+                touched.insert(self.store.region_of(index));
+                r.dirty = false;
+            }
+        }
+
+        for region in &touched {
+            self.store.sync_region(region)?;
+        }
+
+        self.store.write_meta(self.generation, self.rule_id.as_deref())?;
+
+        Ok(())
+    }
+
+    //
+
+    fn write(&mut self, p: &Point, v: T) -> Result<(), GridError> {
+        let index = p.to_subgrid_index(Self::L_I);
+        self.ensure_resident(&index)?;
+
+        if !self.resident.contains(&index) {
+            self.evict_to(self.resident.cap() - 1)?;
+            self.resident.put(index.copy(), Resident { sub: SubGrid::new(), dirty: true });
+            self.known.insert(index.copy());
+        }
+
+        let r = self.resident.get_mut(&index).unwrap();
+        r.sub.set(&p.to_subgrid_point(Self::L_I), v);
+        r.dirty = true;
+
+        Ok(())
+    }
+
+    //
+
+    fn ensure_resident(&mut self, index: &SubGridIndex) -> Result<(), GridError> {
+        if self.resident.get(index).is_some() || !self.known.contains(index) {
+            return Ok(());
+        }
+
+        let sub = match self.store.read_chunk(index, &self.codec)? {
+            None => return Err(GridError::ParseError(format!("subgrid [{},{}] is missing from its region file", index.x, index.z))),
+            Some(sub) => sub,
+        };
+
+        self.evict_to(self.resident.cap() - 1)?;
+        self.resident.put(index.copy(), Resident { sub, dirty: false });
+
+        Ok(())
+    }
+
+    fn evict_to(&mut self, len: usize) -> Result<(), GridError> {
+        while self.resident.len() > len {
+            if let Some((index, r)) = self.resident.pop_lru() {
+                if r.dirty {
+                    self.store.write_chunk(&index, &r.sub, &self.codec)?;
+                    self.unsynced.insert(self.store.region_of(&index));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, C, const L: usize> Drop for PagedGrid<T, C, L> where T: Default + Clone + Display, C: CellCodec<T> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+//
+
+#[derive(PartialEq, Eq, Hash, Clone)]
+struct RegionIndex {
+    x: isize,
+    z: isize,
+}
+
+struct RegionStore {
+    dir: PathBuf,
+    l: usize,
+    region_size: usize,
+    files: LruCache<RegionIndex, File, BuildHasherDefault<FxHasher>>,
+}
+
+impl RegionStore {
+    fn open(dir: &Path, l: usize, region_size: usize) -> Result<Self, GridError> {
+        if region_size == 0 {
+            return Err(GridError::InvalidArgument("region size must be at least 1".to_string()));
+        }
+
+        fs::create_dir_all(dir)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            l,
+            region_size,
+            files: LruCache::with_hasher(OPEN_REGIONS, BuildHasherDefault::default()),
+        })
+    }
+
+    fn region_of(&self, index: &SubGridIndex) -> RegionIndex {
+        let r = self.region_size as isize;
+        RegionIndex {
+            x: div_neg_isize_3(index.x, r),
+            z: div_neg_isize_3(index.z, r),
+        }
+    }
+
+    fn region_path(&self, region: &RegionIndex) -> PathBuf {
+        self.dir.join(format!("r.{}.{}.gsr", region.x, region.z))
+    }
+
+    fn entry_pos(&self, index: &SubGridIndex) -> u64 {
+        let r = self.region_size as isize;
+        let slot = (index.x.rem_euclid(r) * r + index.z.rem_euclid(r)) as u64;
+        HEADER_LEN + slot * ENTRY_LEN
+    }
+
+    // Returns every subgrid present in the region files of the directory.
+    fn scan(&self) -> Result<FxHashSet<SubGridIndex>, GridError> {
+        let mut known = FxHashSet::default();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            let region = match parse_region_name(&name.to_string_lossy()) {
+                None => continue,
+                Some(v) => v,
+            };
+
+            let mut f = BufReader::new(File::open(self.region_path(&region))?);
+            self.read_header(&mut f)?;
+
+            let r = self.region_size as isize;
+            for x in 0..r {
+                for z in 0..r {
+                    let (offset, _, _) = read_entry(&mut f)?;
+                    if offset != 0 {
+                        known.insert(SubGridIndex::new(region.x * r + x, region.z * r + z));
+                    }
+                }
+            }
+        }
+
+        Ok(known)
+    }
+
+    fn read_header<R: Read>(&self, r: &mut R) -> Result<(), GridError> {
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != REGION_MAGIC {
+            return Err(GridError::ParseError("not a region file".to_string()));
+        }
+
+        let version = u16::from_le_bytes(read_array(r)?);
+        if version != VERSION {
+            return Err(GridError::ParseError(format!("unsupported region version {}", version)));
+        }
+
+        let l = u32::from_le_bytes(read_array(r)?) as usize;
+        let region_size = u32::from_le_bytes(read_array(r)?) as usize;
+        if l != self.l || region_size != self.region_size {
+            return Err(GridError::ParseError(format!(
+                "region file has subgrid size {} and region size {}, expected {} and {}",
+                l, region_size, self.l, self.region_size,
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn read_chunk<T, C, const L: usize>(&mut self, index: &SubGridIndex, codec: &C) -> Result<Option<SubGrid<T, L>>, GridError>
+        where T: Default + Clone, C: CellCodec<T>
+    {
+        let region = self.region_of(index);
+        if !self.files.contains(&region) && !self.region_path(&region).exists() {
+            return Ok(None);
+        }
+
+        let entry_pos = self.entry_pos(index);
+        let f = self.open_region(&region)?;
+        f.seek(SeekFrom::Start(entry_pos))?;
+        let (offset, len, crc) = read_entry(f)?;
+        if offset == 0 {
+            return Ok(None);
+        }
+
+        f.seek(SeekFrom::Start(offset))?;
+        let buf = read_bytes(f, len as usize)?;
+
+        let mut found = Crc32::new();
+        found.update(&buf);
+        if found.finish() != crc {
+            return Err(GridError::ChecksumMismatch { expected: crc.into(), found: found.finish().into() });
+        }
+
+        let mut chunk = Chunk::new();
+        let mut r = &buf[..];
+        for v in chunk.as_mut_slice() {
+            *v = codec.read_cell(&mut r)?;
+        }
+
+        Ok(Some(SubGrid::from_chunk(chunk)))
+    }
+
+    fn write_chunk<T, C, const L: usize>(&mut self, index: &SubGridIndex, sub: &SubGrid<T, L>, codec: &C) -> Result<(), GridError>
+        where T: Default + Clone, C: CellCodec<T>
+    {
+        let mut buf = Vec::new();
+        for v in sub.values.as_slice() {
+            codec.write_cell(&mut buf, v)?;
+        }
+
+        let mut crc = Crc32::new();
+        crc.update(&buf);
+
+        let entry_pos = self.entry_pos(index);
+        let f = self.open_region(&self.region_of(index))?;
+
+        let offset = f.seek(SeekFrom::End(0))?;
+        f.write_all(&buf)?;
+
+        f.seek(SeekFrom::Start(entry_pos))?;
+        f.write_all(&offset.to_le_bytes())?;
+        f.write_all(&(buf.len() as u32).to_le_bytes())?;
+        f.write_all(&crc.finish().to_le_bytes())?;
+
+        Ok(())
+    }
+
+    // The cached handle of a region file, opening or creating the file if it has none.
+    fn open_region(&mut self, region: &RegionIndex) -> Result<&mut File, GridError> {
+        if !self.files.contains(region) {
+            let f = self.open_region_file(region)?;
+            self.files.put(region.clone(), f);
+        }
+
+        Ok(self.files.get_mut(region).unwrap())
+    }
+
+    fn open_region_file(&self, region: &RegionIndex) -> Result<File, GridError> {
+        let path = self.region_path(region);
+
+        if path.exists() {
+            let mut f = OpenOptions::new().read(true).write(true).open(path)?;
+            self.read_header(&mut f)?;
+            return Ok(f);
+        }
+
+        let mut f = OpenOptions::new().read(true).write(true).create_new(true).open(path)?;
+        {
+            let mut w = BufWriter::new(&mut f);
+            w.write_all(REGION_MAGIC)?;
+            w.write_all(&VERSION.to_le_bytes())?;
+            w.write_all(&(self.l as u32).to_le_bytes())?;
+            w.write_all(&(self.region_size as u32).to_le_bytes())?;
+            for _ in 0..self.region_size * self.region_size {
+                w.write_all(&[0u8; ENTRY_LEN as usize])?;
+            }
+            w.flush()?;
+        }
+
+        Ok(f)
+    }
+
+    fn sync_region(&mut self, region: &RegionIndex) -> Result<(), GridError> {
+        match self.files.get_mut(region) {
+            Some(f) => f.sync_all()?,
+            None => File::open(self.region_path(region))?.sync_all()?,
+        }
+
+        Ok(())
+    }
+
+    fn meta_path(&self) -> PathBuf {
+        self.dir.join("world.gsm")
+    }
+
+    fn read_meta(&self) -> Result<(u64, Option<String>), GridError> {
+        let path = self.meta_path();
+        if !path.exists() {
+            return Ok((0, None));
+        }
+
+        let mut r = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != META_MAGIC {
+            return Err(GridError::ParseError("not a world metadata file".to_string()));
+        }
+
+        let generation = u64::from_le_bytes(read_array(&mut r)?);
+        let len = u32::from_le_bytes(read_array(&mut r)?) as usize;
+        let buf = read_bytes(&mut r, len)?;
+
+        let rule_id = match len {
+            0 => None,
+            _ => Some(String::from_utf8(buf).map_err(|_| GridError::ParseError("rule id is not valid utf-8".to_string()))?),
+        };
+
+        Ok((generation, rule_id))
+    }
+
+    // Written to a temporary file and renamed over the old one so a crash never leaves it torn.
+    fn write_meta(&self, generation: u64, rule_id: Option<&str>) -> Result<(), GridError> {
+        let tmp = self.dir.join("world.gsm.tmp");
+        let rule_id = rule_id.unwrap_or("");
+
+        {
+            let mut f = File::create(&tmp)?;
+            let mut w = BufWriter::new(&mut f);
+            w.write_all(META_MAGIC)?;
+            w.write_all(&generation.to_le_bytes())?;
+            w.write_all(&(rule_id.len() as u32).to_le_bytes())?;
+            w.write_all(rule_id.as_bytes())?;
+            w.flush()?;
+            drop(w);
+            f.sync_all()?;
+        }
+
+        fs::rename(tmp, self.meta_path())?;
+
+        Ok(())
+    }
+}
+
+fn parse_region_name(name: &str) -> Option<RegionIndex> {
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".gsr")?.split('.');
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+
+    match parts.next() {
+        None => Some(RegionIndex { x, z }),
+        Some(_) => None,
+    }
+}
+
+fn read_entry<R: Read>(r: &mut R) -> Result<(u64, u32, u32), GridError> {
+    let offset = u64::from_le_bytes(read_array(r)?);
+    let len = u32::from_le_bytes(read_array(r)?);
+    let crc = u32::from_le_bytes(read_array(r)?);
+    Ok((offset, len, crc))
+}
+
+fn read_array<R: Read, const N: usize>(r: &mut R) -> Result<[u8; N], GridError> {
+    let mut buf = [0u8; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::{LifeRule, Rule};
+    use crate::snapshot::PrimitiveCodec;
+
+    // A fresh directory under the system temp dir, removed when dropped.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("grid-region-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Scratch(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn region_len(dir: &Path) -> u64 {
+        fs::metadata(dir.join("r.0.0.gsr")).unwrap().len()
+    }
+
+    #[test]
+    fn rewrites_append_and_leave_the_old_blob() {
+        let scratch = Scratch::new("append");
+        let mut grid: PagedGrid<u16, _, 4> = PagedGrid::open_with_region_size(&scratch.0, 9, 4, PrimitiveCodec).unwrap();
+
+        grid.try_set(&Point::new(1, 2), 7).unwrap();
+        grid.flush().unwrap();
+        let first = region_len(&scratch.0);
+
+        grid.try_set(&Point::new(1, 2), 8).unwrap();
+        grid.flush().unwrap();
+        let second = region_len(&scratch.0);
+
+        // A chunk of 16 u16 cells is 32 bytes, written again after the first copy.
+        assert_eq!(second, first + 32);
+
+        // The first copy is still intact where it was.
+        let mut f = File::open(scratch.0.join("r.0.0.gsr")).unwrap();
+        f.seek(SeekFrom::Start(first - 32)).unwrap();
+        let old = read_bytes(&mut f, 32).unwrap();
+        assert_eq!(&old[2 * 6..2 * 6 + 2], &7u16.to_le_bytes());
+
+        drop(grid);
+        let mut grid: PagedGrid<u16, _, 4> = PagedGrid::open_with_region_size(&scratch.0, 9, 4, PrimitiveCodec).unwrap();
+        assert_eq!(grid.try_get(&Point::new(1, 2)).unwrap(), Some(&8));
+    }
+
+    #[test]
+    fn rejects_a_torn_blob() {
+        let scratch = Scratch::new("torn");
+        let mut grid: PagedGrid<u16, _, 4> = PagedGrid::open_with_region_size(&scratch.0, 9, 4, PrimitiveCodec).unwrap();
+        grid.try_set(&Point::new(0, 0), 1).unwrap();
+        drop(grid);
+
+        // The blob was cut short after its entry was written.
+        let path = scratch.0.join("r.0.0.gsr");
+        let len = region_len(&scratch.0);
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 1).unwrap();
+
+        let mut grid: PagedGrid<u16, _, 4> = PagedGrid::open_with_region_size(&scratch.0, 9, 4, PrimitiveCodec).unwrap();
+        assert!(grid.try_get(&Point::new(0, 0)).is_err());
+    }
+
+    #[test]
+    fn more_regions_than_open_handles() {
+        let scratch = Scratch::new("handles");
+        let mut grid: PagedGrid<u16, _, 4> = PagedGrid::open_with_region_size(&scratch.0, 9, 1, PrimitiveCodec).unwrap();
+
+        // One subgrid per region, so every write past the ninth evicts and most reopen a file.
+        let n = OPEN_REGIONS as isize * 2;
+        for i in 0..n {
+            grid.try_set(&Point::new(i * 4, 0), i as u16 + 1).unwrap();
+        }
+        for i in 0..n {
+            assert_eq!(grid.try_get(&Point::new(i * 4, 0)).unwrap(), Some(&(i as u16 + 1)));
+        }
+
+        assert!(grid.store.files.len() <= OPEN_REGIONS);
+    }
+
+    #[test]
+    fn rejects_a_zero_region_size() {
+        let scratch = Scratch::new("zero");
+        let r: Result<PagedGrid<u16, _, 4>, _> = PagedGrid::open_with_region_size(&scratch.0, 9, 0, PrimitiveCodec);
+        assert!(matches!(r, Err(GridError::InvalidArgument(_))));
+        assert!(!scratch.0.exists());
+    }
+
+    #[test]
+    fn flush_syncs_regions_written_by_evictions() {
+        let scratch = Scratch::new("evicted");
+        let mut grid: PagedGrid<u16, _, 4> = PagedGrid::open_with_region_size(&scratch.0, 9, 1, PrimitiveCodec).unwrap();
+
+        for i in 0..12 {
+            grid.try_set(&Point::new(i * 4, 0), 1).unwrap();
+        }
+
+        // The three evicted subgrids are each in their own region, none of them resident.
+        assert_eq!(grid.unsynced.len(), 3);
+        assert!(grid.unsynced.iter().all(|r| r.z == 0 && r.x < 3));
+
+        grid.flush().unwrap();
+        assert!(grid.unsynced.is_empty());
+    }
+
+    #[test]
+    fn tick_reports_match_grid() {
+        let scratch = Scratch::new("tick");
+        let rule = LifeRule::conway();
+        let mut paged: PagedGrid<u8, _, 4> = PagedGrid::open_with_region_size(&scratch.0, 9, 2, PrimitiveCodec).unwrap();
+        let mut grid: Grid<u8, 4> = Grid::new();
+
+        // An R-pentomino across a chunk corner.
+        for (x, z) in [(0, -1), (1, -1), (-1, 0), (0, 0), (0, 1)] {
+            paged.try_set(&Point::new(x, z), 1).unwrap();
+            grid.set(&Point::new(x, z), 1);
+        }
+
+        for _ in 0..30 {
+            let expected = grid.step(&rule);
+            let report = paged.tick(|p| Rule::<u8>::neighbors(&rule, p), |p, cur, neighbors| rule.update(p, cur, neighbors)).unwrap();
+
+            assert_eq!(
+                (report.generation, report.births, report.deaths, report.population, report.active_subgrids),
+                (expected.generation, expected.births, expected.deaths, expected.population, expected.active_subgrids),
+            );
+        }
+
+        for (p, v) in grid.iter() {
+            assert_eq!(paged.try_get(&p).unwrap(), Some(v));
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt::Display;
+use std::hash::Hash;
+use std::io::{Read, Write};
+
+use crate::rule::Rule;
+use crate::snapshot::{read_array, read_bytes, CellCodec, Crc32Reader, Crc32Writer};
+use crate::stats::TickReport;
+use crate::{Grid, GridError, Point};
+
+// Layout, all integers little-endian:
+//
+//   magic "GRPL" | version u16 | seed u64 | rule id (u32 len, utf-8) | snapshot (u64 len, bytes)
+//   | edit count u64 | edits (generation u64, x i64, z i64, cell) | hash count u64 | hashes u64
+//   | crc32 u32
+
+const MAGIC: &[u8; 4] = b"GRPL";
+const VERSION: u16 = 1;
+
+/// A `set` made between ticks, while the grid was at `generation`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit<T> {
+    pub generation: u64,
+    pub point: Point,
+    pub value: T,
+}
+
+/// Everything needed to reproduce a run: the starting grid, the rule, the edits made along the way
+/// and the hash of every generation reached. Generators are not recorded, so a grid that relies on
+/// one must have it installed again, typically from `seed`, before its log can be replayed.
+#[derive(Clone, Debug)]
+pub struct ReplayLog<T> {
+    seed: u64,
+    rule_id: String,
+    snapshot: Vec<u8>,
+    edits: Vec<Edit<T>>,
+    // hashes[i] is `cycle_hash().positional` once the run reached the snapshot's generation + i,
+    // before any edits made at that generation.
+    hashes: Vec<u64>,
+}
+
+/// Runs a grid under one rule and records a [`ReplayLog`] of it.
+pub struct Recorder<T, R, const L: usize> where T: Default + Clone + Display {
+    grid: Grid<T, L>,
+    rule: R,
+    log: ReplayLog<T>,
+}
+
+impl<T, R, const L: usize> Recorder<T, R, L> where T: Default + Clone + Display + PartialEq + Hash, R: Rule<T> {
+    /// Starts recording from the grid's current state. `seed` is stored for whatever randomness
+    /// set the run up, such as a soup.
+    pub fn new<C: CellCodec<T>>(mut grid: Grid<T, L>, rule: R, seed: u64, codec: &C) -> Result<Self, GridError> {
+        let mut snapshot = Vec::new();
+        grid.write_snapshot(&mut snapshot, codec)?;
+
+        let log = ReplayLog {
+            seed,
+            rule_id: rule.id(),
+            snapshot,
+            edits: Vec::new(),
+            hashes: vec![grid.cycle_hash().positional],
+        };
+
+        Ok(Self { grid, rule, log })
+    }
+
+    pub fn grid(&self) -> &Grid<T, L> {
+        &self.grid
+    }
+
+    pub fn log(&self) -> &ReplayLog<T> {
+        &self.log
+    }
+
+    /// Sets a cell and records the edit.
+    pub fn set(&mut self, p: &Point, v: T) {
+        self.log.edits.push(Edit { generation: self.grid.generation, point: p.copy(), value: v.clone() });
+        self.grid.set(p, v);
+    }
+
+    pub fn step(&mut self) -> TickReport {
+        let report = self.grid.step(&self.rule);
+        self.log.hashes.push(self.grid.cycle_hash().positional);
+        report
+    }
+
+    pub fn run(&mut self, n: u64) {
+        for _ in 0..n {
+            self.step();
+        }
+    }
+
+    pub fn finish(self) -> (Grid<T, L>, ReplayLog<T>) {
+        (self.grid, self.log)
+    }
+}
+
+impl<T> ReplayLog<T> where T: Default + Clone + Display + PartialEq + Hash {
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn rule_id(&self) -> &str {
+        &self.rule_id
+    }
+
+    pub fn edits(&self) -> &[Edit<T>] {
+        &self.edits
+    }
+
+    /// The number of generations the recorded run ticked.
+    pub fn generations(&self) -> u64 {
+        self.hashes.len() as u64 - 1
+    }
+
+    /// The grid as it was when the run reached `generation`, before any edits made at it. `rule`
+    /// must have the id the run was recorded with.
+    pub fn replay<R: Rule<T>, C: CellCodec<T>, const L: usize>(&self, rule: &R, codec: &C, generation: u64) -> Result<Grid<T, L>, GridError> {
+        let mut grid = self.start::<R, C, L>(rule, codec)?;
+        let start = grid.generation;
+
+        if generation < start || generation - start > self.generations() {
+            return Err(GridError::InvalidArgument(format!(
+                "generation {} is outside the recorded run, {} to {}", generation, start, start + self.generations()
+            )));
+        }
+
+        let mut edits = self.edits.iter().peekable();
+        while grid.generation < generation {
+            while let Some(edit) = edits.next_if(|e| e.generation == grid.generation) {
+                grid.set(&edit.point, edit.value.clone());
+            }
+
+            grid.step(rule);
+        }
+
+        Ok(grid)
+    }
+
+    /// Replays the whole run, comparing every generation's hash with the recorded one. Returns the
+    /// first generation that differs, or None if the replay matched throughout.
+    pub fn verify<R: Rule<T>, C: CellCodec<T>, const L: usize>(&self, rule: &R, codec: &C) -> Result<Option<u64>, GridError> {
+        let mut grid = self.start::<R, C, L>(rule, codec)?;
+        let mut edits = self.edits.iter().peekable();
+
+        for (i, expected) in self.hashes.iter().enumerate() {
+            if i > 0 {
+                grid.step(rule);
+            }
+
+            if grid.cycle_hash().positional != *expected {
+                return Ok(Some(grid.generation));
+            }
+
+            while let Some(edit) = edits.next_if(|e| e.generation == grid.generation) {
+                grid.set(&edit.point, edit.value.clone());
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn start<R: Rule<T>, C: CellCodec<T>, const L: usize>(&self, rule: &R, codec: &C) -> Result<Grid<T, L>, GridError> {
+        if rule.id() != self.rule_id {
+            return Err(GridError::InvalidRule(format!("run was recorded with {}, not {}", self.rule_id, rule.id())));
+        }
+
+        Grid::read_snapshot(&self.snapshot[..], codec)
+    }
+
+    pub fn write<W: Write, C: CellCodec<T>>(&self, w: W, codec: &C) -> Result<(), GridError> {
+        let mut w = Crc32Writer::new(w);
+
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&self.seed.to_le_bytes())?;
+        w.write_all(&(self.rule_id.len() as u32).to_le_bytes())?;
+        w.write_all(self.rule_id.as_bytes())?;
+        w.write_all(&(self.snapshot.len() as u64).to_le_bytes())?;
+        w.write_all(&self.snapshot)?;
+
+        w.write_all(&(self.edits.len() as u64).to_le_bytes())?;
+        for edit in &self.edits {
+            w.write_all(&edit.generation.to_le_bytes())?;
+            w.write_all(&(edit.point.x as i64).to_le_bytes())?;
+            w.write_all(&(edit.point.z as i64).to_le_bytes())?;
+            codec.write_cell(&mut w, &edit.value)?;
+        }
+
+        w.write_all(&(self.hashes.len() as u64).to_le_bytes())?;
+        for hash in &self.hashes {
+            w.write_all(&hash.to_le_bytes())?;
+        }
+
+        let crc = w.crc();
+        let mut w = w.into_inner();
+        w.write_all(&crc.to_le_bytes())?;
+        w.flush()?;
+
+        Ok(())
+    }
+
+    pub fn read<R: Read, C: CellCodec<T>>(r: R, codec: &C) -> Result<ReplayLog<T>, GridError> {
+        let mut r = Crc32Reader::new(r);
+
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(GridError::ParseError("not a replay log".to_string()));
+        }
+
+        let version = u16::from_le_bytes(read_array(&mut r)?);
+        if version != VERSION {
+            return Err(GridError::ParseError(format!("unsupported replay log version {}", version)));
+        }
+
+        let seed = u64::from_le_bytes(read_array(&mut r)?);
+
+        let len = u32::from_le_bytes(read_array(&mut r)?) as usize;
+        let buf = read_bytes(&mut r, len)?;
+        let rule_id = String::from_utf8(buf).map_err(|_| GridError::ParseError("rule id is not valid utf-8".to_string()))?;
+
+        // Lengths come from the file, so read through `take` rather than trusting them to size a
+        // buffer up front.
+        let len = u64::from_le_bytes(read_array(&mut r)?);
+        let mut snapshot = Vec::new();
+        (&mut r).take(len).read_to_end(&mut snapshot)?;
+        if snapshot.len() as u64 != len {
+            return Err(GridError::ParseError("replay log ends inside its snapshot".to_string()));
+        }
+
+        let count = u64::from_le_bytes(read_array(&mut r)?);
+        let mut edits = Vec::new();
+        for _ in 0..count {
+            let generation = u64::from_le_bytes(read_array(&mut r)?);
+            let x = i64::from_le_bytes(read_array(&mut r)?) as isize;
+            let z = i64::from_le_bytes(read_array(&mut r)?) as isize;
+            let value = codec.read_cell(&mut r)?;
+
+            edits.push(Edit { generation, point: Point::new(x, z), value });
+        }
+
+        let count = u64::from_le_bytes(read_array(&mut r)?);
+        let mut hashes = Vec::new();
+        for _ in 0..count {
+            hashes.push(u64::from_le_bytes(read_array(&mut r)?));
+        }
+        if hashes.is_empty() {
+            return Err(GridError::ParseError("replay log has no generations".to_string()));
+        }
+
+        let found = r.crc();
+        let expected = u32::from_le_bytes(read_array(&mut r.into_inner())?);
+        if expected != found {
+            return Err(GridError::ChecksumMismatch { expected: expected.into(), found: found.into() });
+        }
+
+        Ok(ReplayLog { seed, rule_id, snapshot, edits, hashes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::LifeRule;
+    use crate::snapshot::PrimitiveCodec;
+
+    fn cells(grid: &Grid<u8, 8>) -> Vec<(isize, isize)> {
+        let mut r: Vec<(isize, isize)> = grid.iter().filter(|(_, v)| **v != 0).map(|(p, _)| (p.x, p.z)).collect();
+        r.sort_unstable();
+        r
+    }
+
+    // An R-pentomino run for 30 generations, with edits at generations 0, 10 and 10 again. Returns
+    // the log and the cells at every generation reached.
+    fn record() -> (ReplayLog<u8>, Vec<Vec<(isize, isize)>>) {
+        let mut grid: Grid<u8, 8> = Grid::new();
+        for (x, z) in [(0, -1), (1, -1), (-1, 0), (0, 0), (0, 1)] {
+            grid.set(&Point::new(x, z), 1);
+        }
+
+        let mut recorder = Recorder::new(grid, LifeRule::conway(), 42, &PrimitiveCodec).unwrap();
+        recorder.set(&Point::new(20, 20), 1);
+
+        let mut states = vec![cells(recorder.grid())];
+        for generation in 1..=30 {
+            recorder.step();
+            states.push(cells(recorder.grid()));
+
+            if generation == 10 {
+                recorder.set(&Point::new(-9, 3), 1);
+                recorder.set(&Point::new(-9, 4), 1);
+            }
+        }
+
+        let (_, log) = recorder.finish();
+        (log, states)
+    }
+
+    #[test]
+    fn write_read_replay_matches_the_run() {
+        let (log, states) = record();
+
+        let mut buf = Vec::new();
+        log.write(&mut buf, &PrimitiveCodec).unwrap();
+        let read = ReplayLog::<u8>::read(&buf[..], &PrimitiveCodec).unwrap();
+
+        assert_eq!((read.seed(), read.rule_id(), read.generations()), (42, "B3/S23", 30));
+        assert_eq!(read.edits(), log.edits());
+        assert_eq!(read.verify::<_, _, 8>(&LifeRule::conway(), &PrimitiveCodec).unwrap(), None);
+
+        // `replay` stops before the edits made at the generation it reaches.
+        assert_eq!(cells(&read.replay(&LifeRule::conway(), &PrimitiveCodec, 30).unwrap()), states[30]);
+        assert_eq!(cells(&read.replay(&LifeRule::conway(), &PrimitiveCodec, 11).unwrap()), states[11]);
+        assert_eq!(cells(&read.replay(&LifeRule::conway(), &PrimitiveCodec, 0).unwrap()), states[0][..5]);
+    }
+
+    #[test]
+    fn verify_finds_divergence() {
+        let (mut log, _) = record();
+        log.hashes[12] ^= 1;
+
+        assert_eq!(log.verify::<_, _, 8>(&LifeRule::conway(), &PrimitiveCodec).unwrap(), Some(12));
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let (log, _) = record();
+        let mut buf = Vec::new();
+        log.write(&mut buf, &PrimitiveCodec).unwrap();
+
+        let mut flipped = buf.clone();
+        flipped[buf.len() / 2] ^= 0x10;
+        assert!(ReplayLog::<u8>::read(&flipped[..], &PrimitiveCodec).is_err());
+
+        assert!(ReplayLog::<u8>::read(&buf[..buf.len() - 1], &PrimitiveCodec).is_err());
+
+        // A rule id length far past the end of the file.
+        let mut long = buf.clone();
+        long[14..18].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(ReplayLog::<u8>::read(&long[..], &PrimitiveCodec), Err(GridError::Io(_))));
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::{GridError, Offset, Point, Update};
+
+/// A cellular automaton rule, in the same terms as the closures `Grid::tick` takes.
+pub trait Rule<T> where T: Default + Clone + Display {
+    /// A string that identifies the rule, stored with snapshots and replays. For rules that can
+    /// be parsed, it should round-trip through the parser.
+    fn id(&self) -> String;
+
+    fn neighbors(&self, p: &Point) -> &Vec<Offset>;
+
+    fn update(&self, p: &Point, cur: Option<&T>, neighbors: Vec<Option<&T>>) -> Vec<Update<T>>;
+}
+
+/// Cell types a two-state rule can run on. Absent cells count as dead.
+pub trait LifeState: Default + Clone + Display {
+    fn alive() -> Self;
+    fn dead() -> Self;
+    fn is_alive(&self) -> bool;
+}
+
+macro_rules! life_state {
+    ($($t:ty),*) => {
+        $(
+            impl LifeState for $t {
+                fn alive() -> Self { 1 }
+                fn dead() -> Self { 0 }
+                fn is_alive(&self) -> bool { *self != 0 }
+            }
+        )*
+    };
+}
+
+life_state!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl LifeState for bool {
+    fn alive() -> Self { true }
+    fn dead() -> Self { false }
+    fn is_alive(&self) -> bool { *self }
+}
+
+/// An outer-totalistic rule on the Moore neighbourhood, written in B/S notation such as
+/// `B3/S23`. The older S/B form `23/3` is also accepted.
+pub struct LifeRule {
+    birth: [bool; 9],
+    survival: [bool; 9],
+    neighbors: Vec<Offset>,
+}
+
+impl LifeRule {
+    pub fn new(birth: &[u8], survival: &[u8]) -> Result<Self, GridError> {
+        let mut r = Self {
+            birth: [false; 9],
+            survival: [false; 9],
+            neighbors: moore_offsets(),
+        };
+
+        for n in birth {
+            *r.birth.get_mut(*n as usize).ok_or_else(|| GridError::InvalidRule(format!("birth count {} is above 8", n)))? = true;
+        }
+        for n in survival {
+            *r.survival.get_mut(*n as usize).ok_or_else(|| GridError::InvalidRule(format!("survival count {} is above 8", n)))? = true;
+        }
+
+        if r.birth[0] {
+            return Err(GridError::InvalidRule("B0 rules would fill the infinite plane".to_string()));
+        }
+
+        Ok(r)
+    }
+
+    /// Conway's Game of Life, `B3/S23`.
+    pub fn conway() -> Self {
+        Self::new(&[3], &[2, 3]).unwrap()
+    }
+
+    pub fn is_birth(&self, live_neighbors: usize) -> bool {
+        self.birth[live_neighbors]
+    }
+
+    pub fn is_survival(&self, live_neighbors: usize) -> bool {
+        self.survival[live_neighbors]
+    }
+}
+
+impl<T> Rule<T> for LifeRule where T: LifeState {
+    fn id(&self) -> String {
+        self.to_string()
+    }
+
+    fn neighbors(&self, _: &Point) -> &Vec<Offset> {
+        &self.neighbors
+    }
+
+    fn update(&self, p: &Point, cur: Option<&T>, neighbors: Vec<Option<&T>>) -> Vec<Update<T>> {
+        let live = neighbors.into_iter().filter(|v| v.is_some_and(|v| v.is_alive())).count();
+
+        match cur.is_some_and(|v| v.is_alive()) {
+            false if self.birth[live] => vec![Update::new(p.copy(), |_| Some(T::alive()))],
+            true if !self.survival[live] => vec![Update::new(p.copy(), |_| Some(T::dead()))],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Display for LifeRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "B")?;
+        for (n, b) in self.birth.iter().enumerate() {
+            if *b { write!(f, "{}", n)?; }
+        }
+
+        write!(f, "/S")?;
+        for (n, s) in self.survival.iter().enumerate() {
+            if *s { write!(f, "{}", n)?; }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for LifeRule {
+    type Err = GridError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.trim().split('/').collect();
+        if parts.len() != 2 {
+            return Err(GridError::ParseError(format!("rule '{}' is not of the form B3/S23", s)));
+        }
+
+        let (birth, survival) = match (strip_prefix_ci(parts[0], 'b'), strip_prefix_ci(parts[1], 's')) {
+            (Some(b), Some(s)) => (b, s),
+            (None, None) => (parts[1], parts[0]),
+            _ => return Err(GridError::ParseError(format!("rule '{}' is not of the form B3/S23", s))),
+        };
+
+        LifeRule::new(&parse_counts(birth)?, &parse_counts(survival)?)
+    }
+}
+
+fn strip_prefix_ci(s: &str, c: char) -> Option<&str> {
+    s.strip_prefix(c).or_else(|| s.strip_prefix(c.to_ascii_uppercase()))
+}
+
+fn parse_counts(s: &str) -> Result<Vec<u8>, GridError> {
+    s.chars()
+        .map(|c| c.to_digit(10).map(|d| d as u8).ok_or_else(|| GridError::ParseError(format!("'{}' is not a neighbour count", c))))
+        .collect()
+}
+
+/// The eight offsets of the radius-1 Moore neighbourhood.
+pub fn moore_offsets() -> Vec<Offset> {
+    let mut r = Vec::with_capacity(8);
+
+    for x in -1..=1 {
+        for z in -1..=1 {
+            if x != 0 || z != 0 {
+                r.push(Offset::new(x, z));
+            }
+        }
+    }
+
+    r
+}
@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::rule::Rule;
+use crate::stats::TickReport;
+use crate::Grid;
+
+/// Checked after every generation of a run; the first one that holds, in the order given, ends
+/// the run. A still life is an oscillation of period 1, so it stops an `Oscillation` condition
+/// too, and is reported as `StillLife` only when that comes first in the list.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum StopCondition {
+    /// No cell differs from `T::default()`.
+    Extinction,
+    /// A generation left the grid unchanged.
+    StillLife,
+    /// The grid returned to a state seen at most this many generations ago, including the one
+    /// just before it.
+    Oscillation(u64),
+    /// More than this many cells differ from `T::default()`.
+    PopulationLimit(usize),
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum StopReason {
+    /// The requested number of generations ran.
+    Generations,
+    /// The `run_until` predicate returned true.
+    Predicate,
+    /// The `run_for` duration passed.
+    Timeout,
+    Extinction,
+    StillLife,
+    Oscillation { period: u64 },
+    PopulationLimit { population: usize },
+}
+
+#[derive(Clone, Debug)]
+pub struct RunReport {
+    pub reason: StopReason,
+    /// Generations ticked by this run.
+    pub generations: u64,
+    /// The grid's generation counter when the run stopped.
+    pub generation: u64,
+    pub elapsed: Duration,
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display + PartialEq {
+    /// Ticks once under `rule` and records its id as the grid's rule id.
+    pub fn step<R: Rule<T>>(&mut self, rule: &R) -> TickReport {
+        let report = self.tick_under(rule);
+        self.rule_id = Some(rule.id());
+        report
+    }
+
+    fn tick_under<R: Rule<T>>(&mut self, rule: &R) -> TickReport {
+        self.tick(|p| rule.neighbors(p), |p, cur, neighbors| rule.update(p, cur, neighbors))
+    }
+}
+
+enum Limit<F> {
+    Generations(u64),
+    Predicate(F),
+    Duration(Duration),
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display + PartialEq + Hash {
+    /// Runs `n` generations, or fewer if one of `stop` holds first.
+    pub fn run<R: Rule<T>>(&mut self, rule: &R, n: u64, stop: &[StopCondition]) -> RunReport {
+        self.run_limited(rule, Limit::Generations::<fn(&Grid<T, L>) -> bool>(n), stop)
+    }
+
+    /// Runs until `predicate` returns true for the grid after a generation, or one of `stop`
+    /// holds. With neither able to trigger this never returns.
+    pub fn run_until<R: Rule<T>, F: FnMut(&Grid<T, L>) -> bool>(&mut self, rule: &R, predicate: F, stop: &[StopCondition]) -> RunReport {
+        self.run_limited(rule, Limit::Predicate(predicate), stop)
+    }
+
+    /// Runs until `duration` has passed, checked between generations, or one of `stop` holds.
+    pub fn run_for<R: Rule<T>>(&mut self, rule: &R, duration: Duration, stop: &[StopCondition]) -> RunReport {
+        self.run_limited(rule, Limit::Duration::<fn(&Grid<T, L>) -> bool>(duration), stop)
+    }
+
+    fn run_limited<R: Rule<T>, F: FnMut(&Grid<T, L>) -> bool>(&mut self, rule: &R, mut limit: Limit<F>, stop: &[StopCondition]) -> RunReport {
+        let start = Instant::now();
+        let start_generation = self.generation;
+
+        let max_period = stop.iter().map(|c| match c {
+            StopCondition::StillLife => 1,
+            StopCondition::Oscillation(p) => *p,
+            _ => 0,
+        }).max().unwrap_or(0);
+
+        let mut history = VecDeque::new();
+        if max_period > 0 {
+            history.push_back(self.cycle_hash().positional);
+        }
+
+        self.rule_id = Some(rule.id());
+
+        let reason = loop {
+            if let Limit::Generations(n) = limit {
+                if self.generation - start_generation >= n {
+                    break StopReason::Generations;
+                }
+            }
+
+            self.tick_under(rule);
+
+            if let Some(reason) = self.check_stop(stop, &mut history, max_period) {
+                break reason;
+            }
+
+            match &mut limit {
+                Limit::Generations(_) => {}
+                Limit::Predicate(predicate) => if predicate(self) { break StopReason::Predicate },
+                Limit::Duration(duration) => if start.elapsed() >= *duration { break StopReason::Timeout },
+            }
+        };
+
+        RunReport {
+            reason,
+            generations: self.generation - start_generation,
+            generation: self.generation,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    fn check_stop(&mut self, stop: &[StopCondition], history: &mut VecDeque<u64>, max_period: u64) -> Option<StopReason> {
+        let needs_population = stop.iter().any(|c| matches!(c, StopCondition::Extinction | StopCondition::PopulationLimit(_)));
+        let population = match needs_population {
+            true => self.count_population().0,
+            false => 0,
+        };
+
+        // The period is the distance back to the most recent matching state.
+        let period = match max_period {
+            0 => None,
+            _ => {
+                let hash = self.cycle_hash().positional;
+                let period = history.iter().rev().position(|h| *h == hash).map(|i| i as u64 + 1);
+
+                history.push_back(hash);
+                if history.len() as u64 > max_period {
+                    history.pop_front();
+                }
+
+                period
+            }
+        };
+
+        for condition in stop {
+            match condition {
+                StopCondition::Extinction if population == 0 => return Some(StopReason::Extinction),
+                StopCondition::StillLife if period == Some(1) => return Some(StopReason::StillLife),
+                StopCondition::Oscillation(max) => {
+                    if let Some(period) = period {
+                        if period <= *max {
+                            return Some(StopReason::Oscillation { period });
+                        }
+                    }
+                }
+                StopCondition::PopulationLimit(max) if population > *max => return Some(StopReason::PopulationLimit { population }),
+                _ => {}
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::LifeRule;
+    use crate::Point;
+
+    fn block() -> Grid<u8, 8> {
+        let mut grid = Grid::new();
+        for (x, z) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            grid.set(&Point::new(x, z), 1);
+        }
+        grid
+    }
+
+    #[test]
+    fn list_order_decides_between_still_life_and_oscillation() {
+        let rule = LifeRule::conway();
+
+        let report = block().run(&rule, 10, &[StopCondition::StillLife, StopCondition::Oscillation(4)]);
+        assert_eq!(report.reason, StopReason::StillLife);
+
+        let report = block().run(&rule, 10, &[StopCondition::Oscillation(4), StopCondition::StillLife]);
+        assert_eq!(report.reason, StopReason::Oscillation { period: 1 });
+        assert_eq!(report.generations, 1);
+    }
+
+    #[test]
+    fn run_records_the_rule_id() {
+        let rule = LifeRule::conway();
+        let mut grid = block();
+
+        let report = grid.run(&rule, 3, &[]);
+        assert_eq!(report.reason, StopReason::Generations);
+        assert_eq!(grid.generation(), 3);
+        assert_eq!(grid.rule_id(), Some(Rule::<u8>::id(&rule).as_str()));
+    }
+}
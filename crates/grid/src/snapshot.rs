@@ -0,0 +1,367 @@
+use std::fmt::Display;
+use std::io::{self, Read, Write};
+
+use crate::chunk::Chunk;
+use crate::{Grid, GridError, SubGrid, SubGridIndex};
+
+// Layout, all integers little-endian:
+//
+//   magic "GSNP" | version u16 | L u32 | generation u64 | rule id (u8 flag, u32 len, utf-8)
+//   | chunk count u64 | chunks (x i64, z i64, L*L cells in x-major order) | crc32 u32
+//
+// The checksum covers every byte before it.
+
+const MAGIC: &[u8; 4] = b"GSNP";
+const VERSION: u16 = 1;
+
+/// Encodes single cells of a snapshot. Implement this for custom cell types, or use
+/// [`PrimitiveCodec`] for integers and `bool`.
+pub trait CellCodec<T> {
+    fn write_cell<W: Write>(&self, w: &mut W, v: &T) -> io::Result<()>;
+    fn read_cell<R: Read>(&self, r: &mut R) -> io::Result<T>;
+}
+
+/// Writes primitives as their fixed-width little-endian bytes; `usize` and `isize` are always
+/// stored as 64 bits so snapshots are portable between platforms.
+pub struct PrimitiveCodec;
+
+macro_rules! primitive_codec {
+    ($($t:ty => $repr:ty),*) => {
+        $(
+            impl CellCodec<$t> for PrimitiveCodec {
+                fn write_cell<W: Write>(&self, w: &mut W, v: &$t) -> io::Result<()> {
+                    w.write_all(&(*v as $repr).to_le_bytes())
+                }
+
+                fn read_cell<R: Read>(&self, r: &mut R) -> io::Result<$t> {
+                    let mut buf = [0u8; std::mem::size_of::<$repr>()];
+                    r.read_exact(&mut buf)?;
+                    Ok(<$repr>::from_le_bytes(buf) as $t)
+                }
+            }
+        )*
+    };
+}
+
+primitive_codec!(
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, usize => u64,
+    i8 => i8, i16 => i16, i32 => i32, i64 => i64, isize => i64
+);
+
+impl CellCodec<bool> for PrimitiveCodec {
+    fn write_cell<W: Write>(&self, w: &mut W, v: &bool) -> io::Result<()> {
+        w.write_all(&[*v as u8])
+    }
+
+    fn read_cell<R: Read>(&self, r: &mut R) -> io::Result<bool> {
+        let mut buf = [0u8; 1];
+        r.read_exact(&mut buf)?;
+        Ok(buf[0] != 0)
+    }
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display {
+    /// Streams the grid to `w` one subgrid at a time. `w` is not buffered here, so wrap files in a
+    /// `BufWriter`.
+    pub fn write_snapshot<W: Write, C: CellCodec<T>>(&self, w: W, codec: &C) -> Result<(), GridError> {
+        let mut w = Crc32Writer::new(w);
+
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&(L as u32).to_le_bytes())?;
+        w.write_all(&self.generation.to_le_bytes())?;
+
+        match &self.rule_id {
+            None => w.write_all(&[0])?,
+            Some(rule_id) => {
+                w.write_all(&[1])?;
+                w.write_all(&(rule_id.len() as u32).to_le_bytes())?;
+                w.write_all(rule_id.as_bytes())?;
+            }
+        }
+
+        w.write_all(&(self.values.len() as u64).to_le_bytes())?;
+
+        for (sub_index, sub) in &self.values {
+            w.write_all(&(sub_index.x as i64).to_le_bytes())?;
+            w.write_all(&(sub_index.z as i64).to_le_bytes())?;
+
+            for v in sub.values.as_slice() {
+                codec.write_cell(&mut w, v)?;
+            }
+        }
+
+        let crc = w.crc();
+        let mut w = w.into_inner();
+        w.write_all(&crc.to_le_bytes())?;
+        w.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a grid written by [`Grid::write_snapshot`]. Fails if the snapshot was written with a
+    /// different `L`, or if its checksum does not match.
+    pub fn read_snapshot<R: Read, C: CellCodec<T>>(r: R, codec: &C) -> Result<Grid<T, L>, GridError> {
+        let mut r = Crc32Reader::new(r);
+
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(GridError::ParseError("not a grid snapshot".to_string()));
+        }
+
+        let version = u16::from_le_bytes(read_array(&mut r)?);
+        if version != VERSION {
+            return Err(GridError::ParseError(format!("unsupported snapshot version {}", version)));
+        }
+
+        let l = u32::from_le_bytes(read_array(&mut r)?);
+        if l as usize != L {
+            return Err(GridError::ParseError(format!("snapshot has subgrid size {}, expected {}", l, L)));
+        }
+
+        let mut grid = Grid::new();
+        grid.generation = u64::from_le_bytes(read_array(&mut r)?);
+
+        let [has_rule_id] = read_array(&mut r)?;
+        if has_rule_id != 0 {
+            let len = u32::from_le_bytes(read_array(&mut r)?) as usize;
+            let buf = read_bytes(&mut r, len)?;
+
+            let rule_id = String::from_utf8(buf).map_err(|_| GridError::ParseError("rule id is not valid utf-8".to_string()))?;
+            grid.rule_id = Some(rule_id);
+        }
+
+        let count = u64::from_le_bytes(read_array(&mut r)?);
+
+        for _ in 0..count {
+            let x = i64::from_le_bytes(read_array(&mut r)?) as isize;
+            let z = i64::from_le_bytes(read_array(&mut r)?) as isize;
+
+            let mut chunk = Chunk::new();
+            for v in chunk.as_mut_slice() {
+                *v = codec.read_cell(&mut r)?;
+            }
+
+            grid.values.insert(SubGridIndex::new(x, z), SubGrid::from_chunk(chunk));
+        }
+
+        let found = r.crc();
+        let expected = u32::from_le_bytes(read_array(&mut r.into_inner())?);
+        if expected != found {
+            return Err(GridError::ChecksumMismatch { expected: expected.into(), found: found.into() });
+        }
+
+        Ok(grid)
+    }
+}
+
+pub(crate) fn read_array<R: Read, const N: usize>(r: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+// Reads `len` bytes, growing the buffer only as they arrive, so that a corrupt length fails at the
+// end of the input rather than allocating up front.
+pub(crate) fn read_bytes<R: Read>(r: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    r.by_ref().take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(buf)
+}
+
+//
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+
+        table[i] = c;
+        i += 1;
+    }
+
+    table
+}
+
+/// Running CRC-32 (IEEE), as used by zlib and PNG.
+#[derive(Clone, Copy)]
+pub(crate) struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    pub(crate) fn new() -> Self {
+        Self { state: 0xFFFFFFFF }
+    }
+
+    pub(crate) fn update(&mut self, buf: &[u8]) {
+        for b in buf {
+            self.state = CRC32_TABLE[((self.state ^ *b as u32) & 0xFF) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u32 {
+        self.state ^ 0xFFFFFFFF
+    }
+}
+
+pub(crate) struct Crc32Writer<W: Write> {
+    inner: W,
+    crc: Crc32,
+}
+
+impl<W: Write> Crc32Writer<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner, crc: Crc32::new() }
+    }
+
+    pub(crate) fn crc(&self) -> u32 {
+        self.crc.finish()
+    }
+
+    pub(crate) fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for Crc32Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.crc.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub(crate) struct Crc32Reader<R: Read> {
+    inner: R,
+    crc: Crc32,
+}
+
+impl<R: Read> Crc32Reader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self { inner, crc: Crc32::new() }
+    }
+
+    pub(crate) fn crc(&self) -> u32 {
+        self.crc.finish()
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for Crc32Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.crc.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Point;
+
+    fn sample() -> Grid<u16, 4> {
+        let mut grid = Grid::new();
+        for (x, z, v) in [(0, 0, 1), (3, 3, 2), (-1, 5, 300), (-9, -9, 65535)] {
+            grid.set(&Point::new(x, z), v);
+        }
+        grid.generation = 17;
+        grid.rule_id = Some("B3/S23".to_string());
+        grid
+    }
+
+    fn bytes(grid: &Grid<u16, 4>) -> Vec<u8> {
+        let mut data = Vec::new();
+        grid.write_snapshot(&mut data, &PrimitiveCodec).unwrap();
+        data
+    }
+
+    fn read(data: &[u8]) -> Result<Grid<u16, 4>, GridError> {
+        Grid::read_snapshot(data, &PrimitiveCodec)
+    }
+
+    #[test]
+    fn round_trip() {
+        let grid = sample();
+        let copy = read(&bytes(&grid)).unwrap();
+
+        assert_eq!(copy.generation(), 17);
+        assert_eq!(copy.rule_id(), Some("B3/S23"));
+        assert_eq!(copy.values.len(), grid.values.len());
+        for (p, v) in grid.iter() {
+            assert_eq!(copy.get(&p), Some(v));
+        }
+
+        let mut empty: Grid<u16, 4> = Grid::new();
+        empty.rule_id = None;
+        let copy = read(&bytes(&empty)).unwrap();
+        assert_eq!((copy.values.len(), copy.rule_id()), (0, None));
+    }
+
+    #[test]
+    fn rejects_bit_flips() {
+        let data = bytes(&sample());
+
+        // Past the magic, version and subgrid size, which are rejected by name.
+        for i in 10..data.len() {
+            for bit in [0, 7] {
+                let mut data = data.clone();
+                data[i] ^= 1 << bit;
+                assert!(read(&data).is_err(), "accepted a flip of bit {} of byte {}", bit, i);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_truncation() {
+        let data = bytes(&sample());
+        for len in 0..data.len() {
+            assert!(read(&data[..len]).is_err(), "accepted {} of {} bytes", len, data.len());
+        }
+    }
+
+    #[test]
+    fn rejects_bad_header() {
+        let mut data = bytes(&sample());
+        data[0] = b'X';
+        assert!(matches!(read(&data), Err(GridError::ParseError(e)) if e.contains("not a grid snapshot")));
+
+        let mut data = bytes(&sample());
+        data[4..6].copy_from_slice(&2u16.to_le_bytes());
+        assert!(matches!(read(&data), Err(GridError::ParseError(e)) if e.contains("version 2")));
+
+        let data = bytes(&sample());
+        assert!(Grid::<u16, 8>::read_snapshot(&data[..], &PrimitiveCodec).is_err());
+    }
+
+    #[test]
+    fn huge_rule_id_length_fails_without_allocating() {
+        let mut data = bytes(&sample());
+        // The rule id length follows magic, version, L, generation and the flag.
+        data[19..23].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(read(&data), Err(GridError::Io(_))));
+    }
+}
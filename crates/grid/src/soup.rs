@@ -0,0 +1,200 @@
+use std::fmt::Display;
+
+use crate::generator::{hash_point, mix64, probability_threshold};
+use crate::{Grid, GridError, Point};
+
+/// Symmetry of a soup, in apgsearch notation. `C4` and `D8` need a square rectangle.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Symmetry {
+    /// No symmetry.
+    C1,
+    /// Invariant under a 180° rotation.
+    C2,
+    /// Invariant under 90° rotations.
+    C4,
+    /// Mirrored across the vertical axis.
+    D2,
+    /// Mirrored across both axes.
+    D4,
+    /// Invariant under every rotation and reflection of the square.
+    D8,
+}
+
+/// A random fill of the rectangle from `start` to `end` (inclusive). Each cell is live with
+/// probability `density`, and live cells take a state drawn from `states` by weight. The same seed
+/// always produces the same soup.
+pub struct Soup<T> where T: Clone {
+    start: Point,
+    end: Point,
+    density: f64,
+    states: Vec<(T, u32)>,
+    seed: u64,
+    symmetry: Symmetry,
+}
+
+impl<T> Soup<T> where T: Clone {
+    pub fn new(start: Point, end: Point, density: f64, state: T, seed: u64) -> Self {
+        Self {
+            start,
+            end,
+            density,
+            states: vec![(state, 1)],
+            seed,
+            symmetry: Symmetry::C1,
+        }
+    }
+
+    /// Replaces the single live state with a weighted distribution.
+    pub fn with_states(mut self, states: Vec<(T, u32)>) -> Self {
+        self.states = states;
+        self
+    }
+
+    pub fn with_symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.symmetry = symmetry;
+        self
+    }
+
+    /// Every live cell of the soup.
+    pub fn cells(&self) -> Result<Vec<(Point, T)>, GridError> {
+        let w = self.end.x - self.start.x + 1;
+        let h = self.end.z - self.start.z + 1;
+
+        if w <= 0 || h <= 0 {
+            return Err(GridError::InvalidArgument(format!("soup rectangle from {} to {} is empty", self.start, self.end)));
+        }
+        if (self.symmetry == Symmetry::C4 || self.symmetry == Symmetry::D8) && w != h {
+            return Err(GridError::InvalidArgument(format!("{:?} soups need a square rectangle, got {}x{}", self.symmetry, w, h)));
+        }
+
+        let total: u64 = self.states.iter().map(|(_, weight)| *weight as u64).sum();
+        if total == 0 {
+            return Err(GridError::InvalidArgument("soup has no states with a positive weight".to_string()));
+        }
+
+        let threshold = probability_threshold(self.density);
+        let mut r = Vec::new();
+
+        for u in 0..w {
+            for v in 0..h {
+                let (ru, rv) = self.orbit_representative(u, v, w, h);
+                let bits = hash_point(self.seed, ru, rv);
+
+                if bits >= threshold {
+                    continue;
+                }
+
+                let mut pick = mix64(bits) % total;
+                for (state, weight) in &self.states {
+                    if pick < *weight as u64 {
+                        r.push((Point::new(self.start.x + u, self.start.z + v), state.clone()));
+                        break;
+                    }
+                    pick -= *weight as u64;
+                }
+            }
+        }
+
+        Ok(r)
+    }
+
+    pub fn apply<const L: usize>(&self, grid: &mut Grid<T, L>) -> Result<(), GridError> where T: Default + Display {
+        for (p, v) in self.cells()? {
+            grid.set(&p, v);
+        }
+
+        Ok(())
+    }
+
+    // The smallest image of (u, v) under the symmetry group, so that every cell of an orbit hashes
+    // the same way.
+    fn orbit_representative(&self, u: isize, v: isize, w: isize, h: isize) -> (isize, isize) {
+        let (mu, mv) = (w - 1 - u, h - 1 - v);
+
+        let images: &[(isize, isize)] = match self.symmetry {
+            Symmetry::C1 => &[(u, v)],
+            Symmetry::C2 => &[(u, v), (mu, mv)],
+            Symmetry::C4 => &[(u, v), (mv, u), (mu, mv), (v, mu)],
+            Symmetry::D2 => &[(u, v), (mu, v)],
+            Symmetry::D4 => &[(u, v), (mu, v), (u, mv), (mu, mv)],
+            Symmetry::D8 => &[(u, v), (mv, u), (mu, mv), (v, mu), (mu, v), (u, mv), (v, u), (mv, mu)],
+        };
+
+        *images.iter().min().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_hash::FxHashSet;
+
+    fn soup(symmetry: Symmetry, seed: u64) -> Soup<u8> {
+        Soup::new(Point::new(-5, 3), Point::new(6, 14), 0.5, 1, seed).with_symmetry(symmetry)
+    }
+
+    // Live cells relative to the rectangle's corner.
+    fn offsets(soup: &Soup<u8>) -> FxHashSet<(isize, isize)> {
+        soup.cells().unwrap().iter().map(|(p, _)| (p.x + 5, p.z - 3)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_soup() {
+        let a = soup(Symmetry::C1, 7).cells().unwrap();
+        let b = soup(Symmetry::C1, 7).cells().unwrap();
+        assert_eq!(a, b);
+        assert_ne!(offsets(&soup(Symmetry::C1, 7)), offsets(&soup(Symmetry::C1, 8)));
+    }
+
+    #[test]
+    fn density_bounds() {
+        assert!(Soup::new(Point::new(0, 0), Point::new(9, 4), 0.0, 1u8, 3).cells().unwrap().is_empty());
+
+        let full = Soup::new(Point::new(0, 0), Point::new(9, 4), 1.0, 1u8, 3).cells().unwrap();
+        assert_eq!(full.len(), 50);
+        assert!(full.iter().all(|(p, _)| p.x >= 0 && p.x <= 9 && p.z >= 0 && p.z <= 4));
+    }
+
+    #[test]
+    fn symmetric_soups_are_invariant() {
+        // The 12x12 rectangle's generating transforms for each group, in offsets from its corner.
+        fn rotate((u, v): (isize, isize)) -> (isize, isize) { (11 - v, u) }
+        fn half_turn((u, v): (isize, isize)) -> (isize, isize) { (11 - u, 11 - v) }
+        fn mirror_u((u, v): (isize, isize)) -> (isize, isize) { (11 - u, v) }
+        fn mirror_v((u, v): (isize, isize)) -> (isize, isize) { (u, 11 - v) }
+
+        type Map = fn((isize, isize)) -> (isize, isize);
+        let cases: [(Symmetry, &[Map]); 5] = [
+            (Symmetry::C2, &[half_turn]),
+            (Symmetry::C4, &[rotate]),
+            (Symmetry::D2, &[mirror_u]),
+            (Symmetry::D4, &[mirror_u, mirror_v]),
+            (Symmetry::D8, &[rotate, mirror_u]),
+        ];
+
+        for (symmetry, transforms) in cases {
+            let cells = offsets(&soup(symmetry, 11));
+            assert!(!cells.is_empty());
+
+            for t in transforms {
+                let moved: FxHashSet<(isize, isize)> = cells.iter().map(|c| t(*c)).collect();
+                assert_eq!(moved, cells, "{:?}", symmetry);
+            }
+        }
+
+        // Without symmetry, the same seed is very unlikely to come out symmetric.
+        let cells = offsets(&soup(Symmetry::C1, 11));
+        assert_ne!(cells.iter().map(|c| half_turn(*c)).collect::<FxHashSet<_>>(), cells);
+    }
+
+    #[test]
+    fn states_come_from_the_distribution() {
+        let cells = Soup::new(Point::new(0, 0), Point::new(31, 31), 0.8, 0u8, 5)
+            .with_states(vec![(3, 1), (7, 2), (9, 0)])
+            .cells()
+            .unwrap();
+
+        let states: FxHashSet<u8> = cells.iter().map(|(_, v)| *v).collect();
+        assert_eq!(states, [3, 7].iter().copied().collect());
+    }
+}
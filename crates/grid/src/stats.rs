@@ -0,0 +1,200 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::{Grid, SubGrid, SubGridIndex};
+
+/// Statistics for one generation, as returned by `Grid::tick`. A cell is live when it differs
+/// from `T::default()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TickReport {
+    /// The generation the tick produced.
+    pub generation: u64,
+    pub births: usize,
+    pub deaths: usize,
+    pub population: usize,
+    /// Subgrids allocated after the tick.
+    pub subgrids: usize,
+    /// Subgrids holding at least one live cell.
+    pub active_subgrids: usize,
+    pub subgrids_allocated: usize,
+    pub subgrids_freed: usize,
+    pub elapsed: Duration,
+}
+
+/// Collects tick reports into a time series that can be written out for plotting.
+#[derive(Default)]
+pub struct StatsRecorder {
+    reports: Vec<TickReport>,
+}
+
+const COLUMNS: &str = "generation,births,deaths,population,subgrids,active_subgrids,subgrids_allocated,subgrids_freed,elapsed_us";
+
+impl StatsRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, report: TickReport) {
+        self.reports.push(report);
+    }
+
+    pub fn reports(&self) -> &[TickReport] {
+        &self.reports
+    }
+
+    pub fn clear(&mut self) {
+        self.reports.clear();
+    }
+
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "{}", COLUMNS)?;
+
+        for r in &self.reports {
+            writeln!(
+                w, "{},{},{},{},{},{},{},{},{}",
+                r.generation, r.births, r.deaths, r.population, r.subgrids, r.active_subgrids,
+                r.subgrids_allocated, r.subgrids_freed, r.elapsed.as_micros(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_json_lines<W: Write>(&self, mut w: W) -> io::Result<()> {
+        for r in &self.reports {
+            writeln!(
+                w,
+                "{{\"generation\":{},\"births\":{},\"deaths\":{},\"population\":{},\"subgrids\":{},\"active_subgrids\":{},\"subgrids_allocated\":{},\"subgrids_freed\":{},\"elapsed_us\":{}}}",
+                r.generation, r.births, r.deaths, r.population, r.subgrids, r.active_subgrids,
+                r.subgrids_allocated, r.subgrids_freed, r.elapsed.as_micros(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, const L: usize> Grid<T, L> where T: Default + Clone + Display + PartialEq {
+    // Live cells and the number of subgrids holding any. Only subgrids written outside `tick`
+    // since they were last counted are scanned.
+    pub(crate) fn count_population(&mut self) -> (usize, usize) {
+        let default = T::default();
+        let mut population = 0;
+        let mut active = 0;
+
+        for sub in self.values.values_mut() {
+            let n = live_cells(sub, &default);
+            if n > 0 {
+                population += n;
+                active += 1;
+            }
+        }
+
+        (population, active)
+    }
+
+    pub(crate) fn free_empty(&mut self) -> usize {
+        let default = T::default();
+        let empty: Vec<SubGridIndex> = self.values.iter_mut()
+            .filter_map(|(index, sub)| match live_cells(sub, &default) {
+                0 => Some(index.copy()),
+                _ => None,
+            })
+            .collect();
+
+        if let Some(history) = &mut self.history {
+            for index in &empty {
+                history.record(&self.values, index);
+            }
+        }
+
+        for index in &empty {
+            self.values.remove(index);
+        }
+
+        let freed = empty.len();
+        if freed > 0 {
+            self.invalidate_subgrids_to_scan();
+        }
+
+        freed
+    }
+}
+
+fn live_cells<T: Default + Clone + PartialEq, const L: usize>(sub: &mut SubGrid<T, L>, default: &T) -> usize {
+    match sub.live {
+        Some(n) => n,
+        None => {
+            let n = sub.values.as_slice().iter().filter(|v| *v != default).count();
+            sub.live = Some(n);
+            n
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::LifeRule;
+    use crate::{Offset, Point, Update};
+
+    // A full scan, to check the counts `tick` keeps against.
+    fn census(grid: &Grid<u8, 4>) -> (usize, usize) {
+        let population = grid.iter().filter(|(_, v)| **v != 0).count();
+        let active = grid.values.values().filter(|sub| sub.values.as_slice().iter().any(|v| *v != 0)).count();
+        (population, active)
+    }
+
+    fn tick_with(grid: &mut Grid<u8, 4>, updates: fn(&Point) -> Vec<Update<u8>>) -> TickReport {
+        let offsets: Vec<Offset> = Vec::new();
+        grid.tick(|_| &offsets, |p, _, _| updates(p))
+    }
+
+    #[test]
+    fn births_and_deaths_count_final_changes() {
+        let mut grid: Grid<u8, 4> = Grid::new();
+        grid.set(&Point::new(1, 1), 1);
+
+        let report = tick_with(&mut grid, |p| match (p.x(), p.z()) {
+            // Born and killed again in the same tick.
+            (0, 0) => vec![Update::new(Point::new(0, 0), |_| Some(1)), Update::new(Point::new(0, 0), |_| Some(0))],
+            // Killed and revived.
+            (1, 1) => vec![Update::new(Point::new(1, 1), |_| Some(0)), Update::new(Point::new(1, 1), |_| Some(2))],
+            // Born twice.
+            (2, 2) => vec![Update::new(Point::new(2, 2), |_| Some(1)), Update::new(Point::new(2, 2), |_| Some(3))],
+            _ => Vec::new(),
+        });
+
+        assert_eq!(report.births, 1);
+        assert_eq!(report.deaths, 0);
+        assert_eq!(report.population, 2);
+        assert_eq!(grid.get(&Point::new(2, 2)), Some(&3));
+    }
+
+    #[test]
+    fn population_tracks_ticks_and_writes() {
+        let rule = LifeRule::conway();
+        let mut grid: Grid<u8, 4> = Grid::new();
+        grid.set_free_empty_subgrids(true);
+
+        // An R-pentomino across a chunk corner, plus a lone cell that dies at once.
+        for (x, z) in [(0, -1), (1, -1), (-1, 0), (0, 0), (0, 1), (20, 20)] {
+            grid.set(&Point::new(x, z), 1);
+        }
+
+        for generation in 0..40 {
+            let report = grid.step(&rule);
+            assert_eq!((report.population, report.active_subgrids), census(&grid), "generation {}", generation);
+
+            // Writes outside `tick` drop the cached counts of the chunks they touch.
+            if generation % 7 == 0 {
+                grid.set(&Point::new(generation as isize - 20, 3), 1);
+                grid.set(&Point::new(0, 0), 0);
+            }
+        }
+
+        let counted = grid.count_population();
+        assert_eq!(counted, census(&grid));
+    }
+}
@@ -0,0 +1,235 @@
+use std::fmt::{Display, Write};
+
+use rustc_hash::FxHashMap;
+
+use crate::{Grid, GridError, Point};
+
+type StyleFn<T> = Box<dyn Fn(&T) -> Option<String> + Send + Sync>;
+
+/// Draws grid regions as SVG, one unit per cell with x to the right and z downwards. Cells are
+/// grouped by the style `style` gives their state, and each group is drawn as a single path of
+/// rectangles, merging runs of cells along x and then identical runs down z.
+pub struct SvgRenderer<T> {
+    style: StyleFn<T>,
+    scale: f64,
+    labels: Vec<(Point, String)>,
+}
+
+impl<T> SvgRenderer<T> where T: Default + Clone + Display {
+    /// `style` returns the SVG presentation attributes for a state, such as `fill="#000"`, or None
+    /// to leave its cells out. The style of `T::default()` is used for the background.
+    pub fn new<F: Fn(&T) -> Option<String> + Send + Sync + 'static>(style: F) -> Self {
+        Self {
+            style: Box::new(style),
+            scale: 10.0,
+            labels: Vec::new(),
+        }
+    }
+
+    /// The size of a cell in the document's `width` and `height`; the `viewBox` stays in cells.
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Writes `text` centred on the cell at `p`, if it is inside the rendered rectangle.
+    pub fn with_label(mut self, p: Point, text: &str) -> Self {
+        self.labels.push((p, text.to_string()));
+        self
+    }
+
+    /// Renders every allocated subgrid, over the same whole-subgrid bounds `Grid::print` uses.
+    pub fn render<const L: usize>(&self, grid: &Grid<T, L>) -> Result<String, GridError> {
+        if grid.values.is_empty() {
+            return Err(GridError::InvalidArgument("cannot render an empty grid".to_string()));
+        }
+
+        let (min, max) = grid.find_grid_point_bounds();
+        self.render_rect(grid, &min, &Point::new(max.x - 1, max.z - 1))
+    }
+
+    /// Renders the rectangle from `start` to `end`, inclusive.
+    pub fn render_rect<const L: usize>(&self, grid: &Grid<T, L>, start: &Point, end: &Point) -> Result<String, GridError> {
+        if end.x < start.x || end.z < start.z {
+            return Err(GridError::InvalidArgument(format!("svg rectangle from {} to {} is empty", start, end)));
+        }
+
+        let (w, h) = ((end.x - start.x + 1) as usize, (end.z - start.z + 1) as usize);
+        if w.checked_mul(h).is_none_or(|n| n > MAX_CELLS) {
+            return Err(GridError::InvalidArgument(format!("a {}x{} region is too large to render", w, h)));
+        }
+
+        let background = (self.style)(&T::default());
+
+        // Styles are numbered in order of first appearance, so output is stable between runs.
+        let mut styles: Vec<String> = Vec::new();
+        let mut lookup: FxHashMap<String, u32> = FxHashMap::default();
+        let mut cells: Vec<Option<u32>> = vec![None; w * h];
+
+        for (p, v) in grid.iter_rect(start, end) {
+            let style = match (self.style)(v) {
+                Some(style) if Some(&style) != background.as_ref() => style,
+                _ => continue,
+            };
+
+            let i = *lookup.entry(style).or_insert_with_key(|style| {
+                styles.push(style.clone());
+                styles.len() as u32 - 1
+            });
+            cells[(p.z - start.z) as usize * w + (p.x - start.x) as usize] = Some(i);
+        }
+
+        let mut paths = vec![String::new(); styles.len()];
+        for (style, x, z, rw, rh) in merge(&cells, w, h) {
+            write!(paths[style as usize], "M{} {}h{}v{}h-{}z", x, z, rw, rh, rw).unwrap();
+        }
+
+        let mut s = String::new();
+        writeln!(s, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" shape-rendering="crispEdges">"#,
+            w as f64 * self.scale, h as f64 * self.scale, w, h).unwrap();
+
+        if let Some(style) = &background {
+            writeln!(s, r#"<rect width="{}" height="{}" {}/>"#, w, h, style).unwrap();
+        }
+        for (style, d) in styles.iter().zip(&paths) {
+            writeln!(s, r#"<path {} d="{}"/>"#, style, d).unwrap();
+        }
+
+        for (p, text) in &self.labels {
+            if p.x < start.x || p.x > end.x || p.z < start.z || p.z > end.z {
+                continue;
+            }
+
+            writeln!(s, r#"<text x="{}.5" y="{}.5" font-size="0.8" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+                p.x - start.x, p.z - start.z, escape(text)).unwrap();
+        }
+
+        s.push_str("</svg>\n");
+        Ok(s)
+    }
+}
+
+// Matches the image renderer's limit, to turn a mistaken region into an error.
+const MAX_CELLS: usize = 1 << 28;
+
+// Rectangles as (style, x, z, width, height). Each row is split into runs of one style, and a run
+// extends the rectangle above it when that rectangle has exactly the same columns and style.
+fn merge(cells: &[Option<u32>], w: usize, h: usize) -> Vec<(u32, usize, usize, usize, usize)> {
+    let mut done = Vec::new();
+    let mut open: FxHashMap<(usize, usize, u32), usize> = FxHashMap::default();
+
+    for z in 0..h {
+        let row = &cells[z * w..(z + 1) * w];
+        let mut next = FxHashMap::default();
+
+        let mut x = 0;
+        while x < w {
+            let style = match row[x] {
+                None => {
+                    x += 1;
+                    continue;
+                }
+                Some(style) => style,
+            };
+
+            let x0 = x;
+            while x < w && row[x] == Some(style) {
+                x += 1;
+            }
+
+            let z0 = open.remove(&(x0, x, style)).unwrap_or(z);
+            next.insert((x0, x, style), z0);
+        }
+
+        for ((x0, x1, style), z0) in open.drain() {
+            done.push((style, x0, z0, x1 - x0, z - z0));
+        }
+        open = next;
+    }
+
+    for ((x0, x1, style), z0) in open {
+        done.push((style, x0, z0, x1 - x0, h - z0));
+    }
+
+    done.sort_unstable();
+    done
+}
+
+fn escape(text: &str) -> String {
+    let mut s = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => s.push_str("&amp;"),
+            '<' => s.push_str("&lt;"),
+            '>' => s.push_str("&gt;"),
+            '"' => s.push_str("&quot;"),
+            c => s.push(c),
+        }
+    }
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(rows: &[&str]) -> Vec<Option<u32>> {
+        rows.concat().chars().map(|c| c.to_digit(10)).collect()
+    }
+
+    #[test]
+    fn merge_keeps_an_l_shape_apart() {
+        let rects = merge(&cells(&["0.", "00"]), 2, 2);
+        assert_eq!(rects, vec![(0, 0, 0, 1, 1), (0, 0, 1, 2, 1)]);
+    }
+
+    #[test]
+    fn merge_joins_identical_rows() {
+        let rects = merge(&cells(&[".00.", ".00.", ".00.", "...."]), 4, 4);
+        assert_eq!(rects, vec![(0, 1, 0, 2, 3)]);
+
+        // Runs of different styles over the same columns stay apart, and a run ending at the last
+        // row is closed there.
+        let rects = merge(&cells(&["11", "22", "22"]), 2, 3);
+        assert_eq!(rects, vec![(1, 0, 0, 2, 1), (2, 0, 1, 2, 2)]);
+    }
+
+    fn renderer() -> SvgRenderer<u8> {
+        SvgRenderer::new(|v: &u8| match v {
+            0 => Some(r##"fill="#fff""##.to_string()),
+            1 => Some(r##"fill="#000""##.to_string()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn background_is_one_rect() {
+        let mut grid: Grid<u8, 4> = Grid::new();
+        grid.set(&Point::new(1, 1), 1);
+        grid.set(&Point::new(2, 1), 1);
+        grid.set(&Point::new(3, 3), 2);
+
+        let svg = renderer().render_rect(&grid, &Point::new(0, 0), &Point::new(3, 3)).unwrap();
+        let lines: Vec<&str> = svg.lines().collect();
+        assert_eq!(lines[1], r##"<rect width="4" height="4" fill="#fff"/>"##);
+        assert_eq!(lines[2], r##"<path fill="#000" d="M1 1h2v1h-2z"/>"##);
+        assert_eq!(lines[3], "</svg>");
+    }
+
+    #[test]
+    fn labels_are_escaped_and_clipped() {
+        let mut grid: Grid<u8, 4> = Grid::new();
+        grid.set(&Point::new(0, 0), 1);
+
+        let svg = renderer()
+            .with_label(Point::new(2, 3), "<a & \"b\">")
+            .with_label(Point::new(4, 0), "outside")
+            .with_label(Point::new(-1, 2), "outside")
+            .render_rect(&grid, &Point::new(0, 0), &Point::new(3, 3))
+            .unwrap();
+
+        assert!(svg.contains(r#"<text x="2.5" y="3.5" font-size="0.8" text-anchor="middle" dominant-baseline="central">&lt;a &amp; &quot;b&quot;&gt;</text>"#));
+        assert!(!svg.contains("outside"));
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt::{Display, Write};
+
+use crate::image::Rgb;
+use crate::{Grid, GridError, Point};
+
+/// How cells are packed into terminal characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TermMode {
+    /// Two cells per character, stacked, using `▀`, `▄` and `█`. With colours each cell keeps its
+    /// own.
+    HalfBlock,
+    /// Eight cells per character, two across and four down, as braille dots. With colours the
+    /// character takes the colour of its brightest dot.
+    Braille,
+}
+
+impl TermMode {
+    pub fn dots_per_char(&self) -> (usize, usize) {
+        match self {
+            TermMode::HalfBlock => (1, 2),
+            TermMode::Braille => (2, 4),
+        }
+    }
+
+    /// The smallest scale at which a `width` by `height` region fits in `cols` by `rows`
+    /// characters.
+    pub fn scale_to_fit(&self, width: usize, height: usize, cols: usize, rows: usize) -> usize {
+        let (cw, ch) = self.dots_per_char();
+        width.div_ceil(cols.max(1) * cw).max(height.div_ceil(rows.max(1) * ch)).max(1)
+    }
+}
+
+type ColorFn<T> = Box<dyn Fn(&T) -> Rgb + Send + Sync>;
+
+/// Draws grid regions as text for terminals, much denser than `Grid::print`. Cells run along x to
+/// the right and along z downwards.
+pub struct TermRenderer<T> {
+    mode: TermMode,
+    is_set: Box<dyn Fn(&T) -> bool + Send + Sync>,
+    colors: Option<ColorFn<T>>,
+    scale: usize,
+}
+
+impl<T> TermRenderer<T> where T: Default + Clone + Display {
+    /// Draws the cells for which `is_set` holds, in the terminal's own colours.
+    pub fn new<F: Fn(&T) -> bool + Send + Sync + 'static>(mode: TermMode, is_set: F) -> Self {
+        Self {
+            mode,
+            is_set: Box::new(is_set),
+            colors: None,
+            scale: 1,
+        }
+    }
+
+    /// Colours set cells with ANSI truecolor escapes.
+    pub fn with_colors<F: Fn(&T) -> Rgb + Send + Sync + 'static>(mut self, colors: F) -> Self {
+        self.colors = Some(Box::new(colors));
+        self
+    }
+
+    /// Zooms out, drawing each `scale` by `scale` block of cells as one dot that is set if any
+    /// of its cells is.
+    pub fn with_scale(mut self, scale: usize) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn mode(&self) -> TermMode {
+        self.mode
+    }
+
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+
+    /// The number of cells one character covers across and down.
+    pub fn cells_per_char(&self) -> (usize, usize) {
+        let (cw, ch) = self.mode.dots_per_char();
+        (cw * self.scale, ch * self.scale)
+    }
+
+    /// Renders the rectangle from `start` to `end`, inclusive, one line per row of characters.
+    pub fn render<const L: usize>(&self, grid: &Grid<T, L>, start: &Point, end: &Point) -> Result<String, GridError> {
+        if end.x < start.x || end.z < start.z {
+            return Err(GridError::InvalidArgument(format!("terminal rectangle from {} to {} is empty", start, end)));
+        }
+        if self.scale == 0 {
+            return Err(GridError::InvalidArgument("scale must be at least 1".to_string()));
+        }
+
+        let (cw, ch) = self.mode.dots_per_char();
+        let (w, h) = ((end.x - start.x + 1) as usize, (end.z - start.z + 1) as usize);
+        let (dots_w, dots_h) = (w.div_ceil(self.scale), h.div_ceil(self.scale));
+        let (cols, rows) = (dots_w.div_ceil(cw), dots_h.div_ceil(ch));
+
+        // Whole characters' worth of dots, so that edge characters need no bounds checks.
+        let stride = cols * cw;
+        let mut dots: Vec<Option<Rgb>> = vec![None; stride * rows * ch];
+
+        for (p, v) in grid.iter_rect(start, end) {
+            if !(self.is_set)(v) {
+                continue;
+            }
+
+            let color = self.colors.as_ref().map_or(Rgb::WHITE, |f| f(v));
+            let dot = &mut dots[(p.z - start.z) as usize / self.scale * stride + (p.x - start.x) as usize / self.scale];
+            if dot.is_none_or(|d| brightness(&color) > brightness(&d)) {
+                *dot = Some(color);
+            }
+        }
+
+        let mut s = String::new();
+        for row in 0..rows {
+            let mut line = Line { s: &mut s, fg: None, bg: None, colored: self.colors.is_some() };
+            let dot = |dx: usize, dy: usize, col: usize| dots[(row * ch + dy) * stride + col * cw + dx];
+
+            for col in 0..cols {
+                match self.mode {
+                    TermMode::HalfBlock => match (dot(0, 0, col), dot(0, 1, col)) {
+                        (None, None) => line.put(' ', None, None),
+                        (Some(top), None) => line.put('▀', Some(top), None),
+                        (None, Some(bottom)) => line.put('▄', Some(bottom), None),
+                        (Some(top), Some(bottom)) if top == bottom || !line.colored => line.put('█', Some(top), None),
+                        (Some(top), Some(bottom)) => line.put('▀', Some(top), Some(bottom)),
+                    },
+                    TermMode::Braille => {
+                        let mut bits = 0u32;
+                        let mut color: Option<Rgb> = None;
+
+                        for (i, (dx, dy)) in BRAILLE_DOTS.iter().enumerate() {
+                            if let Some(c) = dot(*dx, *dy, col) {
+                                bits |= 1 << i;
+                                if color.is_none_or(|d| brightness(&c) > brightness(&d)) {
+                                    color = Some(c);
+                                }
+                            }
+                        }
+
+                        match bits {
+                            0 => line.put(' ', None, None),
+                            _ => line.put(char::from_u32(0x2800 + bits).unwrap(), color, None),
+                        }
+                    }
+                }
+            }
+
+            line.end();
+        }
+
+        Ok(s)
+    }
+}
+
+// The dot for each bit of a braille character, as (x, z) within its two by four block.
+const BRAILLE_DOTS: [(usize, usize); 8] = [(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2), (0, 3), (1, 3)];
+
+// Luma first, then the channels, so that picks between colours do not depend on the order cells
+// are visited in.
+fn brightness(c: &Rgb) -> (u8, u8, u8, u8) {
+    (c.luma(), c.0, c.1, c.2)
+}
+
+// Writes one row of characters, emitting colour escapes only when the colour changes.
+struct Line<'a> {
+    s: &'a mut String,
+    fg: Option<Rgb>,
+    bg: Option<Rgb>,
+    colored: bool,
+}
+
+impl Line<'_> {
+    fn put(&mut self, c: char, fg: Option<Rgb>, bg: Option<Rgb>) {
+        if self.colored {
+            // A blank only shows its background.
+            let fg = if c == ' ' { self.fg } else { fg };
+
+            if (fg.is_none() && self.fg.is_some()) || (bg.is_none() && self.bg.is_some()) {
+                self.s.push_str("\x1b[0m");
+                self.fg = None;
+                self.bg = None;
+            }
+            if let Some(fg) = fg.filter(|fg| self.fg != Some(*fg)) {
+                write!(self.s, "\x1b[38;2;{};{};{}m", fg.0, fg.1, fg.2).unwrap();
+                self.fg = Some(fg);
+            }
+            if let Some(bg) = bg.filter(|bg| self.bg != Some(*bg)) {
+                write!(self.s, "\x1b[48;2;{};{};{}m", bg.0, bg.1, bg.2).unwrap();
+                self.bg = Some(bg);
+            }
+        }
+
+        self.s.push(c);
+    }
+
+    fn end(self) {
+        if self.fg.is_some() || self.bg.is_some() {
+            self.s.push_str("\x1b[0m");
+        }
+        self.s.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(cells: &[(isize, isize)]) -> Grid<u8, 8> {
+        let mut grid = Grid::new();
+        for (x, z) in cells {
+            grid.set(&Point::new(*x, *z), 1);
+        }
+        grid
+    }
+
+    fn render(renderer: &TermRenderer<u8>, cells: &[(isize, isize)], end: (isize, isize)) -> String {
+        renderer.render(&grid(cells), &Point::new(0, 0), &Point::new(end.0, end.1)).unwrap()
+    }
+
+    #[test]
+    fn braille_bit_order() {
+        let renderer = TermRenderer::new(TermMode::Braille, |v: &u8| *v != 0);
+        let dots = [(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2), (0, 3), (1, 3)];
+        let chars = ['\u{2801}', '\u{2802}', '\u{2804}', '\u{2808}', '\u{2810}', '\u{2820}', '\u{2840}', '\u{2880}'];
+
+        for (dot, expected) in dots.iter().zip(chars.iter()) {
+            assert_eq!(render(&renderer, &[*dot], (1, 3)), format!("{}\n", expected), "dot {:?}", dot);
+        }
+
+        assert_eq!(render(&renderer, &dots, (1, 3)), "⣿\n");
+        assert_eq!(render(&renderer, &[(2, 4)], (3, 7)), "  \n ⠁\n");
+    }
+
+    #[test]
+    fn half_blocks() {
+        let renderer = TermRenderer::new(TermMode::HalfBlock, |v: &u8| *v != 0);
+        assert_eq!(render(&renderer, &[(0, 0), (1, 1), (2, 0), (2, 1)], (3, 2)), "▀▄█ \n    \n");
+
+        // With colours, differing halves keep their own as foreground and background.
+        let mut g = grid(&[(0, 0)]);
+        g.set(&Point::new(0, 1), 2);
+        let colored = TermRenderer::new(TermMode::HalfBlock, |v: &u8| *v != 0)
+            .with_colors(|v: &u8| if *v == 1 { Rgb(255, 0, 0) } else { Rgb(0, 0, 255) });
+        assert_eq!(
+            colored.render(&g, &Point::new(0, 0), &Point::new(0, 1)).unwrap(),
+            "\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m▀\x1b[0m\n",
+        );
+    }
+
+    #[test]
+    fn scale_folds_blocks_into_dots() {
+        let renderer = TermRenderer::new(TermMode::Braille, |v: &u8| *v != 0).with_scale(2);
+        assert_eq!(renderer.cells_per_char(), (4, 8));
+
+        // A full 2x2 block and a single cell in another block are both one dot.
+        assert_eq!(render(&renderer, &[(0, 0), (1, 0), (0, 1), (1, 1)], (3, 7)), "⠁\n");
+        assert_eq!(render(&renderer, &[(3, 5)], (3, 7)), "⠠\n");
+    }
+
+    #[test]
+    fn scales_to_fit() {
+        assert_eq!(TermMode::HalfBlock.scale_to_fit(100, 100, 80, 24), 3);
+        assert_eq!(TermMode::Braille.scale_to_fit(100, 100, 80, 24), 2);
+        assert_eq!(TermMode::Braille.scale_to_fit(400, 10, 80, 24), 3);
+        assert_eq!(TermMode::HalfBlock.scale_to_fit(5, 5, 80, 24), 1);
+        assert_eq!(TermMode::HalfBlock.scale_to_fit(5, 5, 0, 0), 5);
+    }
+}
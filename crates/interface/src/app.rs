@@ -0,0 +1,452 @@
+use std::io::{self, Write};
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::queue;
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{Clear, ClearType};
+
+use grid::{Grid, GridError, Point};
+use grid::history::HistoryConfig;
+use grid::rule::{LifeRule, Rule};
+use grid::term::{TermMode, TermRenderer};
+
+use crate::files;
+
+/// The grid the viewer runs; a cell is live when it is not 0.
+pub type Cells = Grid<u8, 32>;
+
+// Zoom level 0 draws two cells per character, and each level above it draws braille dots covering
+// twice as many cells across as the level before.
+const MAX_ZOOM: u32 = 12;
+// Speeds run from 1 to 2^MAX_SPEED generations per second.
+const MAX_SPEED: u32 = 12;
+const STATUS_ROWS: u16 = 2;
+// The longest a burst of generations may hold up the next redraw.
+const FRAME: Duration = Duration::from_millis(33);
+
+const HELP: &str = "space run  n step  b back  arrows/hjkl move  HJKL pan  +/- zoom  [/] speed  enter toggle  c centre  f fit  o open  w save  r rule  q quit";
+
+#[derive(Clone, Copy)]
+enum PromptKind {
+    Open,
+    Save,
+    Rule,
+}
+
+struct Prompt {
+    kind: PromptKind,
+    text: String,
+}
+
+/// The viewer, running its grid under any rule on two-state cells that can be shown and parsed
+/// back, as the rule prompt and snapshot rule ids need.
+pub struct App<R = LifeRule> {
+    grid: Cells,
+    rule: R,
+    population: usize,
+    path: Option<PathBuf>,
+    center: (isize, isize),
+    cursor: (isize, isize),
+    zoom: u32,
+    speed: u32,
+    running: bool,
+    next_tick: Instant,
+    size: (u16, u16),
+    prompt: Option<Prompt>,
+    message: Option<String>,
+    clear: bool,
+    quit: bool,
+}
+
+impl<R> App<R> where R: Rule<u8> + Display + FromStr<Err = GridError> {
+    pub fn new(grid: Cells, rule: R, path: Option<PathBuf>, size: (u16, u16)) -> Self {
+        let mut app = Self {
+            grid: Grid::new(),
+            rule,
+            population: 0,
+            path,
+            center: (0, 0),
+            cursor: (0, 0),
+            zoom: 0,
+            speed: 3,
+            running: false,
+            next_tick: Instant::now(),
+            size,
+            prompt: None,
+            message: None,
+            clear: true,
+            quit: false,
+        };
+
+        app.replace_grid(grid);
+        app
+    }
+
+    pub fn quit(&self) -> bool {
+        self.quit
+    }
+
+    /// How long the main loop may wait for input before the next generation is due.
+    pub fn timeout(&self) -> Duration {
+        match self.running {
+            true => self.next_tick.saturating_duration_since(Instant::now()),
+            false => Duration::from_secs(60),
+        }
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        self.size = (cols, rows);
+        self.clear = true;
+        self.scroll_to_cursor();
+    }
+
+    /// Runs the generations that are due, for at most one frame. Returns whether any ran.
+    pub fn advance(&mut self) -> bool {
+        if !self.running || self.next_tick > Instant::now() {
+            return false;
+        }
+
+        let interval = Duration::from_secs(1) / (1 << self.speed);
+        let deadline = Instant::now() + FRAME;
+
+        while self.next_tick <= Instant::now() && Instant::now() < deadline {
+            self.step();
+            self.next_tick += interval;
+        }
+
+        // Drop whatever could not be kept up with rather than racing to catch up later.
+        let now = Instant::now();
+        if self.next_tick < now {
+            self.next_tick = now;
+        }
+
+        true
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+
+        if self.prompt.is_some() {
+            self.handle_prompt_key(key);
+            return;
+        }
+
+        self.message = None;
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        let dot = self.scale() as isize;
+
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Char(' ') => {
+                self.running = !self.running;
+                self.next_tick = Instant::now();
+            }
+            KeyCode::Char('n') | KeyCode::Char('.') => {
+                self.running = false;
+                self.step();
+            }
+            KeyCode::Char('b') | KeyCode::Backspace => {
+                self.running = false;
+                self.back();
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') => self.set_zoom(self.zoom.saturating_sub(1)),
+            KeyCode::Char('-') | KeyCode::Char('_') => self.set_zoom((self.zoom + 1).min(MAX_ZOOM)),
+            KeyCode::Char('[') => self.speed = self.speed.saturating_sub(1),
+            KeyCode::Char(']') => self.speed = (self.speed + 1).min(MAX_SPEED),
+            KeyCode::Left if shift => self.pan(-1, 0),
+            KeyCode::Right if shift => self.pan(1, 0),
+            KeyCode::Up if shift => self.pan(0, -1),
+            KeyCode::Down if shift => self.pan(0, 1),
+            KeyCode::Char('H') => self.pan(-1, 0),
+            KeyCode::Char('L') => self.pan(1, 0),
+            KeyCode::Char('K') => self.pan(0, -1),
+            KeyCode::Char('J') => self.pan(0, 1),
+            KeyCode::Left | KeyCode::Char('h') => self.move_cursor(-dot, 0),
+            KeyCode::Right | KeyCode::Char('l') => self.move_cursor(dot, 0),
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(0, -dot),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(0, dot),
+            KeyCode::Enter | KeyCode::Char('t') => self.toggle(),
+            KeyCode::Char('c') => self.center = self.cursor,
+            KeyCode::Char('f') => self.fit(),
+            KeyCode::Char('o') => self.start_prompt(PromptKind::Open),
+            KeyCode::Char('w') => self.start_prompt(PromptKind::Save),
+            KeyCode::Char('r') => self.start_prompt(PromptKind::Rule),
+            _ => {}
+        }
+    }
+
+    pub fn draw<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        let (cols, rows) = self.grid_size();
+        let (start, end) = self.view();
+        let text = self.renderer().render(&self.grid, &start, &end)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+
+        queue!(out, Hide)?;
+        if self.clear {
+            queue!(out, Clear(ClearType::All))?;
+            self.clear = false;
+        }
+
+        // Every line is a full row of characters, so each overwrites the last frame's.
+        for (row, line) in text.lines().enumerate() {
+            queue!(out, MoveTo(0, row as u16), Print(line))?;
+        }
+
+        let status = format!(
+            " gen {}  pop {}  {}  {}  {}/s  zoom 1:{}  ({}, {})  {}",
+            self.grid.generation(), self.population, self.rule,
+            if self.running { "running" } else { "paused" },
+            1u32 << self.speed, self.scale(), self.cursor.0, self.cursor.1,
+            self.path.as_ref().map_or(String::new(), |p| p.display().to_string()),
+        );
+        let line = match (&self.prompt, &self.message) {
+            (Some(prompt), _) => format!("{}: {}", prompt_label(prompt.kind), prompt.text),
+            (None, Some(message)) => message.clone(),
+            (None, None) => HELP.to_string(),
+        };
+
+        queue!(
+            out,
+            MoveTo(0, rows), SetAttribute(Attribute::Reverse), Print(fit_width(&status, cols)), SetAttribute(Attribute::Reset),
+            MoveTo(0, rows + 1), Clear(ClearType::CurrentLine), Print(fit_width(&line, cols).trim_end()),
+        )?;
+
+        match &self.prompt {
+            Some(_) => queue!(out, MoveTo((line.chars().count() as u16).min(cols.saturating_sub(1)), rows + 1), Show)?,
+            None => {
+                let (cw, ch) = self.renderer().cells_per_char();
+                let col = (self.cursor.0 - start.x()) / cw as isize;
+                let row = (self.cursor.1 - start.z()) / ch as isize;
+                queue!(out, MoveTo(col as u16, row as u16), Show)?;
+            }
+        }
+
+        out.flush()
+    }
+
+    fn handle_prompt_key(&mut self, key: KeyEvent) {
+        let prompt = self.prompt.as_mut().unwrap();
+
+        match key.code {
+            KeyCode::Char(c) => prompt.text.push(c),
+            KeyCode::Backspace => {
+                prompt.text.pop();
+            }
+            KeyCode::Esc => self.prompt = None,
+            KeyCode::Enter => {
+                let prompt = self.prompt.take().unwrap();
+                self.submit(prompt.kind, prompt.text.trim());
+            }
+            _ => {}
+        }
+    }
+
+    fn start_prompt(&mut self, kind: PromptKind) {
+        let text = match kind {
+            PromptKind::Open | PromptKind::Save => self.path.as_ref().map_or(String::new(), |p| p.display().to_string()),
+            PromptKind::Rule => self.rule.to_string(),
+        };
+
+        self.running = false;
+        self.prompt = Some(Prompt { kind, text });
+    }
+
+    fn submit(&mut self, kind: PromptKind, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        let result = match kind {
+            PromptKind::Open => files::load(text.as_ref()).map(|grid| {
+                self.replace_grid(grid);
+                format!("loaded {}", text)
+            }),
+            PromptKind::Save => files::save(&self.grid, text.as_ref()).map(|_| format!("saved {}", text)),
+            PromptKind::Rule => text.parse().map(|rule| {
+                self.set_rule(rule);
+                format!("rule is now {}", self.rule)
+            }),
+        };
+
+        if let PromptKind::Open | PromptKind::Save = kind {
+            if result.is_ok() {
+                self.path = Some(PathBuf::from(text));
+            }
+        }
+
+        self.message = Some(result.unwrap_or_else(|e| e.to_string()));
+    }
+
+    // Takes the rule from a snapshot's rule id when it names one, and otherwise keeps the current
+    // rule.
+    fn replace_grid(&mut self, mut grid: Cells) {
+        grid.set_free_empty_subgrids(true);
+        if let Some(rule) = grid.rule_id().and_then(|id| id.parse().ok()) {
+            self.rule = rule;
+        }
+
+        self.grid = grid;
+        self.population = self.grid.population(|v| *v != 0);
+        self.running = false;
+        self.restart_history();
+        self.fit();
+    }
+
+    fn set_rule(&mut self, rule: R) {
+        self.rule = rule;
+        self.restart_history();
+    }
+
+    // History is recomputed under the rule it was recorded with, so it restarts with a new rule.
+    fn restart_history(&mut self) {
+        self.grid.set_rule_id(Some(self.rule.id()));
+        self.grid.enable_history(HistoryConfig::default());
+    }
+
+    fn step(&mut self) {
+        self.population = self.grid.step(&self.rule).population;
+    }
+
+    fn back(&mut self) {
+        match self.grid.rewind(1, &self.rule) {
+            Ok(()) => self.population = self.grid.population(|v| *v != 0),
+            Err(e) => self.message = Some(e.to_string()),
+        }
+    }
+
+    fn toggle(&mut self) {
+        let p = Point::new(self.cursor.0, self.cursor.1);
+        let live = self.grid.get(&p).is_some_and(|v| *v != 0);
+
+        self.grid.set(&p, if live { 0 } else { 1 });
+        if live {
+            self.population -= 1;
+        } else {
+            self.population += 1;
+        }
+    }
+
+    fn set_zoom(&mut self, zoom: u32) {
+        self.zoom = zoom;
+        self.clear = true;
+        self.scroll_to_cursor();
+    }
+
+    fn move_cursor(&mut self, dx: isize, dz: isize) {
+        self.cursor = (self.cursor.0 + dx, self.cursor.1 + dz);
+        self.scroll_to_cursor();
+    }
+
+    // Moves the view, and the cursor with it, by a quarter of the screen.
+    fn pan(&mut self, dx: isize, dz: isize) {
+        let (start, end) = self.view();
+        let (w, h) = (end.x() - start.x() + 1, end.z() - start.z() + 1);
+        let (dx, dz) = (dx * (w / 4).max(1), dz * (h / 4).max(1));
+
+        self.center = (self.center.0 + dx, self.center.1 + dz);
+        self.cursor = (self.cursor.0 + dx, self.cursor.1 + dz);
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        let (start, end) = self.view();
+
+        if self.cursor.0 < start.x() {
+            self.center.0 -= start.x() - self.cursor.0;
+        } else if self.cursor.0 > end.x() {
+            self.center.0 += self.cursor.0 - end.x();
+        }
+        if self.cursor.1 < start.z() {
+            self.center.1 -= start.z() - self.cursor.1;
+        } else if self.cursor.1 > end.z() {
+            self.center.1 += self.cursor.1 - end.z();
+        }
+    }
+
+    // Centres the live cells at the closest zoom that shows them all.
+    fn fit(&mut self) {
+        let mut live = self.grid.iter_matching(|v| *v != 0).map(|(p, _)| (p.x(), p.z()));
+        let bounds = live.next().map(|first| live.fold((first, first), |(min, max), (x, z)| {
+            ((min.0.min(x), min.1.min(z)), (max.0.max(x), max.1.max(z)))
+        }));
+
+        let (min, max) = match bounds {
+            None => {
+                self.center = (0, 0);
+                self.cursor = (0, 0);
+                self.set_zoom(0);
+                return;
+            }
+            Some(bounds) => bounds,
+        };
+
+        let (w, h) = ((max.0 - min.0 + 1) as usize, (max.1 - min.1 + 1) as usize);
+        let (cols, rows) = self.grid_size();
+        let (cols, rows) = (cols as usize, rows as usize);
+
+        let zoom = match TermMode::HalfBlock.scale_to_fit(w, h, cols, rows) {
+            1 => 0,
+            _ => 1 + TermMode::Braille.scale_to_fit(w, h, cols, rows).next_power_of_two().trailing_zeros(),
+        };
+
+        self.center = ((min.0 + max.0) / 2, (min.1 + max.1) / 2);
+        self.cursor = self.center;
+        self.set_zoom(zoom.min(MAX_ZOOM));
+    }
+
+    fn scale(&self) -> usize {
+        match self.zoom {
+            0 => 1,
+            z => 1 << (z - 1),
+        }
+    }
+
+    fn renderer(&self) -> TermRenderer<u8> {
+        match self.zoom {
+            0 => TermRenderer::new(TermMode::HalfBlock, |v: &u8| *v != 0),
+            _ => TermRenderer::new(TermMode::Braille, |v: &u8| *v != 0).with_scale(self.scale()),
+        }
+    }
+
+    // Columns and rows of characters left for the grid.
+    fn grid_size(&self) -> (u16, u16) {
+        (self.size.0.max(1), self.size.1.saturating_sub(STATUS_ROWS).max(1))
+    }
+
+    // The rectangle of cells on screen, inclusive. Its corner snaps to whole characters, so the
+    // cells a character covers do not change as the view moves.
+    fn view(&self) -> (Point, Point) {
+        let (cw, ch) = self.renderer().cells_per_char();
+        let (cw, ch) = (cw as isize, ch as isize);
+        let (cols, rows) = self.grid_size();
+        let (w, h) = (cols as isize * cw, rows as isize * ch);
+
+        let x = (self.center.0 - w / 2).div_euclid(cw) * cw;
+        let z = (self.center.1 - h / 2).div_euclid(ch) * ch;
+        (Point::new(x, z), Point::new(x + w - 1, z + h - 1))
+    }
+}
+
+fn prompt_label(kind: PromptKind) -> &'static str {
+    match kind {
+        PromptKind::Open => "open",
+        PromptKind::Save => "save",
+        PromptKind::Rule => "rule",
+    }
+}
+
+// Pads or cuts `s` to exactly `cols` characters.
+fn fit_width(s: &str, cols: u16) -> String {
+    let cols = cols as usize;
+    let mut r: String = s.chars().take(cols).collect();
+    let len = r.chars().count();
+    r.extend(std::iter::repeat_n(' ', cols - len));
+    r
+}
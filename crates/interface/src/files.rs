@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use grid::{Grid, GridError, Point};
+use grid::image::{threshold, Image, ImageFormat, ImageRenderer, Rgb};
+use grid::pattern::{Blend, Pattern, Transform};
+use grid::snapshot::PrimitiveCodec;
+
+use crate::app::Cells;
+
+// Patterns are read and written by extension: plaintext for `.cells` and `.txt`, images for the
+// formats `Image::read` knows, and grid snapshots for anything else. Only snapshots keep the
+// generation and rule.
+enum Format {
+    Plaintext,
+    Image(Option<ImageFormat>),
+    Snapshot,
+}
+
+fn format(path: &Path) -> Format {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+
+    match ext.as_str() {
+        "cells" | "txt" => Format::Plaintext,
+        "png" => Format::Image(Some(ImageFormat::Png)),
+        "ppm" => Format::Image(Some(ImageFormat::Ppm)),
+        "pgm" => Format::Image(Some(ImageFormat::Pgm)),
+        "pbm" => Format::Image(None),
+        _ => Format::Snapshot,
+    }
+}
+
+/// Reads a pattern or snapshot. Patterns come in with their corner at the origin.
+pub fn load(path: &Path) -> Result<Cells, GridError> {
+    let pattern = match format(path) {
+        Format::Snapshot => return Grid::read_snapshot(BufReader::new(File::open(path)?), &PrimitiveCodec),
+        Format::Plaintext => Pattern::parse_plaintext(&std::fs::read_to_string(path)?, 1)?,
+        Format::Image(_) => Image::read(BufReader::new(File::open(path)?))?.to_pattern(threshold(128, 1, 0)),
+    };
+
+    let mut grid = Grid::new();
+    grid.paste(&pattern, &Point::new(0, 0), Transform::Identity, Blend::SkipDefault);
+    Ok(grid)
+}
+
+/// Writes the live cells, cropped to their bounds for patterns and images.
+pub fn save(grid: &Cells, path: &Path) -> Result<(), GridError> {
+    let format = match format(path) {
+        Format::Snapshot => return grid.write_snapshot(BufWriter::new(File::create(path)?), &PrimitiveCodec),
+        Format::Image(None) => return Err(GridError::InvalidArgument("PBM can be read but not written".to_string())),
+        format => format,
+    };
+
+    let pattern = Pattern::from_cells(grid.iter_matching(|v| *v != 0).map(|(p, v)| (p, *v)));
+
+    match format {
+        Format::Plaintext => std::fs::write(path, pattern.to_plaintext(|v| *v != 0))?,
+        Format::Image(Some(format)) => {
+            if pattern.width() == 0 {
+                return Err(GridError::InvalidArgument("cannot save an empty grid as an image".to_string()));
+            }
+
+            let image = ImageRenderer::new(|v: &u8| if *v != 0 { Rgb::BLACK } else { Rgb::WHITE })
+                .render(&pattern.to_grid::<32>(), &Point::new(0, 0), &Point::new(pattern.width() as isize - 1, pattern.height() as isize - 1))?;
+            image.write(BufWriter::new(File::create(path)?), format)?;
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
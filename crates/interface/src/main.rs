@@ -1,3 +1,121 @@
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use std::process::exit;
+
+use crossterm::cursor::Show;
+use crossterm::event::{self, Event, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+
+use grid::Grid;
+use grid::rule::LifeRule;
+
+use crate::app::App;
+
+mod app;
+mod files;
+
+const USAGE: &str = "usage: interface [--rule B3/S23] [pattern]
+
+Cells are dead or live, and --rule takes a life-like rule in B/S notation.
+Patterns are read by extension: .cells and .txt as plaintext, .png, .pbm, .pgm and .ppm as images
+with dark pixels live, and anything else as a grid snapshot.";
+
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut rule = LifeRule::conway();
+    let mut path = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--rule" => {
+                i += 1;
+                rule = match args.get(i).map(|r| r.parse()) {
+                    Some(Ok(rule)) => rule,
+                    Some(Err(e)) => fail(&e.to_string()),
+                    None => fail("--rule needs a rule such as B3/S23"),
+                };
+            }
+            arg if path.is_none() && !arg.starts_with('-') => path = Some(PathBuf::from(arg)),
+            arg => fail(&format!("unexpected argument '{}'", arg)),
+        }
+
+        i += 1;
+    }
+
+    let grid = match &path {
+        None => Grid::new(),
+        Some(path) => files::load(path).unwrap_or_else(|e| fail(&format!("cannot load {}: {}", path.display(), e))),
+    };
+
+    let size = terminal::size().unwrap_or((80, 24));
+    if let Err(e) = run(App::new(grid, rule, path, size)) {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    exit(2);
+}
+
+fn run(mut app: App) -> io::Result<()> {
+    let _terminal = Terminal::enter()?;
+    let mut out = BufWriter::new(io::stdout());
+    let mut dirty = true;
+
+    while !app.quit() {
+        if dirty {
+            app.draw(&mut out)?;
+            dirty = false;
+        }
+
+        if event::poll(app.timeout())? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => app.handle_key(key),
+                Event::Resize(cols, rows) => app.resize(cols, rows),
+                _ => {}
+            }
+            dirty = true;
+        }
+
+        // Checked after input too, so that holding a key down cannot stall a running simulation.
+        dirty |= app.advance();
+    }
+
+    Ok(())
+}
+
+// Raw mode on the alternate screen, restored on drop and before a panic message is printed.
+struct Terminal;
+
+impl Terminal {
+    fn enter() -> io::Result<Self> {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore();
+            hook(info);
+        }));
+
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(Terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+fn restore() {
+    let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
 }